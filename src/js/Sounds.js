@@ -0,0 +1,120 @@
+const AudioContext = window.AudioContext || window.webkitAudioContext;
+
+// Plays the game's sound effects. Sounds arrive as blorb resources (AIFF or
+// OGG data) and only get decoded the first time they're played. Any sound
+// the browser can't decode just counts as finished straight away.
+class Sounds {
+  constructor(onFinished) {
+    this.onFinished = onFinished;
+    this._ctx = null;
+    this._data = new Map();
+    this._decoded = new Map();
+    this._playing = new Map();
+  }
+
+  context() {
+    if (!this._ctx && typeof AudioContext !== 'undefined') {
+      this._ctx = new AudioContext();
+    }
+
+    return this._ctx;
+  }
+
+  // 1 is a high bleep, 2 a low one
+  bleep(number) {
+    const ctx = this.context();
+    if (!ctx) return;
+
+    const osc = ctx.createOscillator();
+    osc.frequency.value = (number === 1) ? 880 : 220;
+    osc.connect(ctx.destination);
+    osc.start();
+    osc.stop(ctx.currentTime + 0.1);
+  }
+
+  load(number, b64) {
+    const bytes = Uint8Array.from(atob(b64), c => c.charCodeAt(0));
+
+    this._data.set(number, bytes.buffer);
+    this._decoded.delete(number);
+  }
+
+  decode(number) {
+    if (this._decoded.has(number)) return Promise.resolve(this._decoded.get(number));
+    if (!this._data.has(number)) return Promise.reject(new Error(`No sound ${number}`));
+
+    // decodeAudioData detaches the buffer it gets, so hand it a copy
+    const data = this._data.get(number).slice(0);
+
+    return new Promise((resolve, reject) => {
+      this.context().decodeAudioData(data, resolve, reject);
+    }).then((buffer) => {
+      this._decoded.set(number, buffer);
+      return buffer;
+    });
+  }
+
+  // volume is 1-8, and repeats of 255 loops until it gets stopped
+  start(number, volume, repeats) {
+    const ctx = this.context();
+    this.stop(number);
+
+    if (!ctx) {
+      this.onFinished(number);
+      return;
+    }
+
+    this.decode(number).then((buffer) => {
+      const gain = ctx.createGain();
+      gain.gain.value = volume / 8;
+      gain.connect(ctx.destination);
+
+      let remaining = repeats;
+
+      const play = () => {
+        const source = ctx.createBufferSource();
+        source.buffer = buffer;
+        source.loop = (repeats === 255);
+        source.connect(gain);
+
+        source.onended = () => {
+          // stopped early, which doesn't count as finishing
+          if (this._playing.get(number) !== source) return;
+
+          remaining -= 1;
+
+          if (remaining > 0) {
+            play();
+          } else {
+            this._playing.delete(number);
+            this.onFinished(number);
+          }
+        };
+
+        this._playing.set(number, source);
+        source.start();
+      };
+
+      play();
+    }).catch(() => this.onFinished(number));
+  }
+
+  stop(number) {
+    const source = this._playing.get(number);
+    this._playing.delete(number);
+
+    if (source) source.stop();
+  }
+
+  unload(number) {
+    this.stop(number);
+    this._data.delete(number);
+    this._decoded.delete(number);
+  }
+
+  clear() {
+    [...this._data.keys()].forEach(number => this.unload(number));
+  }
+}
+
+export default Sounds;
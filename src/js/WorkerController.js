@@ -18,8 +18,8 @@ class WorkerWrapper extends EventEmitter {
     });
   }
 
-  load(filename, file) {
-    this.send('load', { filename, file }, [file]);
+  load(filename, file, size) {
+    this.send('load', { filename, file, size }, [file]);
   }
 
   terminate() {
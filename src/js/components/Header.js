@@ -8,6 +8,19 @@ const Header = (props) => {
 
   document.title = (props.left) ? `${title} - ${props.left}` : title;
 
+  // v4+ games draw their own status line in the upper window
+  if (props.upper.length) {
+    return (
+      <div className="header">
+        <div className="upper">
+          {props.upper.map((line, index) => (
+            <div key={index}>{line || '\u00A0'}</div>
+          ))}
+        </div>
+      </div>
+    );
+  }
+
   return (
     <div className="header">
       <div>
@@ -35,6 +48,7 @@ export default connect(
   state => ({
     left: state.transcript.header.left,
     right: state.transcript.header.right,
+    upper: state.transcript.upper,
     canUndo: !state.transcript.quit && state.transcript.moves.length > 1,
     canRedo: !state.transcript.quit && !!state.transcript.undos.length,
   }),
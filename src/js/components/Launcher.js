@@ -40,16 +40,21 @@ class Launcher extends Component {
       const buf = reader.result;
       const view = new DataView(buf);
 
-      const isValid = (view.byteLength > 64) &&
+      // blorbs (.zblorb) wrap the game file, it gets checked once loaded
+      const isBlorb = (view.byteLength > 12) &&
+        view.getUint32(0x0) === 0x464F524D && // FORM
+        view.getUint32(0x8) === 0x49465253;   // IFRS
+
+      const isValid = isBlorb || ((view.byteLength > 64) &&
         view.getUint16(0x4) <= view.byteLength &&
         view.getUint16(0x6) <= view.byteLength &&
         view.getUint16(0x8) <= view.byteLength &&
         view.getUint16(0xA) <= view.byteLength &&
         view.getUint16(0xC) <= view.byteLength &&
-        view.getUint16(0xE) <= view.byteLength &&
-        view.getUint16(0x1A) * 2 <= view.byteLength;
+        view.getUint16(0xE) <= view.byteLength);
 
-      const isRightVersion = view.getUint8(0x0) <= 3;
+      const isRightVersion = isBlorb ||
+        [3, 4, 5, 7, 8].includes(view.getUint8(0x0));
 
       if (isValid && isRightVersion) {
         const name = (!!~file.name.indexOf('.'))
@@ -62,7 +67,7 @@ class Launcher extends Component {
       } else {
         const msg = (isValid && !isRightVersion)
         // intentional typo?
-          ? 'Encursted only supports version 3 zcode game files right now :('
+          ? 'Encursted only supports version 3, 4, 5, 7 and 8 zcode game files right now :('
           : 'Something went wrong validating this file. Are you sure its a game?';
 
         this.props.openModal(
@@ -1,6 +1,8 @@
 import React from 'react';
 import WorkerController from './WorkerController';
 import { Graph } from './Rooms';
+import measure from './measure';
+import Sounds from './Sounds';
 
 import Restore from './components/Restore';
 import ErrorModal from './components/ErrorModal';
@@ -27,6 +29,37 @@ function debounce(fn, delay = 500) {
 }
 
 
+// maps a keydown event to a zscii code for read_char (null = ignore it)
+const SPECIAL_KEYS = {
+  Enter: 13,
+  Backspace: 8,
+  Delete: 8,
+  Escape: 27,
+  ArrowUp: 129,
+  ArrowDown: 130,
+  ArrowLeft: 131,
+  ArrowRight: 132,
+};
+
+// the keypad has its own codes, but only as terminating keys for line input
+function keypadKey(ev) {
+  const digit = /^Numpad([0-9])$/.exec(ev.code);
+  return (digit) ? 145 + Number(digit[1]) : zsciiKey(ev);
+}
+
+function zsciiKey(ev) {
+  if (has(SPECIAL_KEYS, ev.key)) return SPECIAL_KEYS[ev.key];
+
+  const fkey = /^F([0-9]{1,2})$/.exec(ev.key);
+  if (fkey && fkey[1] >= 1 && fkey[1] <= 12) return 132 + Number(fkey[1]);
+
+  if (ev.key.length !== 1 || ev.ctrlKey || ev.metaKey) return null;
+
+  const code = ev.key.charCodeAt(0);
+  return (code >= 32 && code <= 126) ? code : 63;
+}
+
+
 class LocalStore {
   constructor(id) {
     this._id = id;
@@ -67,11 +100,53 @@ class LocalStore {
 const url = process.env.ENCRUSTEDROOT + 'worker.js'
 
 const worker = new WorkerController(url);
+const sounds = new Sounds(number => worker.send('sound_finished', number));
 
 let storage; // file specific localstorage
+let auxStorage; // auxiliary files, these survive restarts
 let graph;
 let saves = [];
 let last_input = '';
+let timer = null; // interrupt timer for timed input
+let keyListener = null; // keydown handler while read_char is waiting
+let partial = ''; // input typed so far when a timed read gets interrupted
+let preload = ''; // input the game put in the text buffer before reading
+
+
+// the input field of the active move, if there is one
+function currentInput() {
+  const inputs = document.querySelectorAll('.moves input');
+  const el = inputs[inputs.length - 1];
+  return el ? el.value : '';
+}
+
+// screen size reported to the game: as many fixed-width characters as fit
+// across the transcript, and no paging since it just scrolls
+function screenSize() {
+  const el = document.querySelector('.transcript');
+  const width = (el) ? el.clientWidth : Math.min(window.innerWidth, 750);
+  const columns = Math.floor(width / measure('0', '14.5px monospace'));
+
+  return { lines: 255, columns: Math.max(1, Math.min(columns, 255)) };
+}
+
+function startTimer(time) {
+  if (!time) return;
+
+  clearInterval(timer);
+  timer = setInterval(() => {
+    partial = currentInput();
+    worker.send('tick', partial);
+  }, time * 100);
+}
+
+function stopTimedInput() {
+  clearInterval(timer);
+  timer = null;
+
+  if (keyListener) window.removeEventListener('keydown', keyListener);
+  keyListener = null;
+}
 
 
 const middleware = store => next => (action) => {
@@ -84,7 +159,10 @@ const middleware = store => next => (action) => {
     const [saveMap, cancelSave] = debounce(() => {storage.set('map', graph.serialize())});
 
     worker.on('print', (text) => {
-      dispatch({ type: 'TS::TEXT', text });
+      // an interrupt printed during a timed read, so carry the input over
+      // (or the game handed over some input to start the line with)
+      dispatch({ type: 'TS::TEXT', text, input: (timer) ? partial : preload });
+      preload = '';
       cancelSave();
       const countScript = store.getState().transcript.moves.length;
       storage.set(`script::${countScript}`, "<span>&nbsp;" + last_input + "</span><br>" + text);
@@ -96,8 +174,52 @@ const middleware = store => next => (action) => {
     });
 
     worker.on('header', data => dispatch({ type: 'TS::HEADER', data }));
+    worker.on('window', data => dispatch({ type: 'TS::WINDOW', data }));
     worker.on('quit', () => dispatch({ type: 'TS::QUIT' }));
 
+    // read_char wants a single keypress, so grab the next key directly
+    worker.on('read_char', (time) => {
+      keyListener = (ev) => {
+        const key = zsciiKey(ev);
+        if (key === null) return;
+
+        ev.preventDefault();
+        stopTimedInput();
+        worker.send('key', key);
+      };
+
+      window.addEventListener('keydown', keyListener);
+      startTimer(Number(time));
+    });
+
+    // function keys (etc) that also finish line input, instead of enter
+    worker.on('terminators', (data) => {
+      const terminators = JSON.parse(data);
+
+      keyListener = (ev) => {
+        const key = keypadKey(ev);
+        if (!terminators.includes(key)) return;
+
+        ev.preventDefault();
+        dispatch({ type: 'TS::SUBMIT', input: currentInput(), key });
+      };
+
+      window.addEventListener('keydown', keyListener);
+    });
+
+    worker.on('preload', (text) => { preload = text; });
+
+    // timed reads call back into the interrupt routine every `time` 1/10s
+    worker.on('timed', (time) => {
+      partial = preload;
+      startTimer(Number(time));
+    });
+    worker.on('aborted', () => {
+      stopTimedInput();
+      dispatch({ type: 'TS::ABORT', input: partial });
+      partial = '';
+    });
+
     // short timer here to make sure the text gets rendered quickest
     worker.on('map', data => setTimeout(() => {
       const [id, name] = JSON.parse(data);
@@ -135,11 +257,50 @@ const middleware = store => next => (action) => {
       storage.set('saves', JSON.stringify(saves));
     });
 
+    // sound effects, numbered by their blorb resource
+    worker.on('bleep', number => sounds.bleep(Number(number)));
+    worker.on('sound_load', (data) => {
+      const [number, _kind, contents] = JSON.parse(data);
+      sounds.load(number, contents);
+    });
+    worker.on('sound_start', (data) => {
+      const [number, volume, repeats] = JSON.parse(data);
+      sounds.start(number, volume, repeats);
+    });
+    worker.on('sound_stop', number => sounds.stop(Number(number)));
+    worker.on('sound_unload', number => sounds.unload(Number(number)));
+
     worker.on('restore', () => {
       dispatch({ type: 'MODAL::SHOW', child: <Restore /> });
     });
 
+    // auxiliary files (high scores, settings...) just live in localstorage
+    worker.on('save_aux', (data) => {
+      const [name, contents] = JSON.parse(data);
+      auxStorage.set(name, contents);
+    });
+
+    worker.on('restore_aux', (name) => {
+      worker.send('restore_aux', auxStorage.get(name) || '');
+    });
+
+    // files that couldn't be loaded as a game (like an unsupported blorb)
+    worker.on('load_error', (msg) => {
+      const text = `Couldn't load that game: ${msg}`;
+      dispatch({ type: 'MODAL::SHOW', child: <ErrorModal err={{ stack: '' }} msg={text} /> });
+    });
+
+    // errors from the zmachine itself come through as json, the game is
+    // paused where it went wrong and can still be undone
     worker.on('error', (err) => {
+      if (typeof err === 'string') {
+        const { msg, stack } = JSON.parse(err);
+        const text = `The game stopped with an error: ${msg}. Undo to go back to the last turn.`;
+
+        dispatch({ type: 'MODAL::SHOW', child: <ErrorModal err={{ stack }} msg={text} /> });
+        return;
+      }
+
       dispatch({ type: 'MODAL::SHOW', child: <ErrorModal err={err} /> });
     });
 
@@ -150,11 +311,13 @@ const middleware = store => next => (action) => {
   // loads a file into the zmachine
   function load(filename, file) {
     // loads into worker
-    worker.load(filename, file);
+    worker.load(filename, file, screenSize());
 
     // set up UI
+    sounds.clear();
     last_input = '';
     storage = new LocalStore(filename);
+    auxStorage = new LocalStore(`aux::${filename}`);
     graph = Graph.deserialize(storage.get('map'));
     saves = JSON.parse(storage.get('saves') || '[]');
 
@@ -220,6 +383,7 @@ const middleware = store => next => (action) => {
       break;
 
     case 'TS::RESTART':
+      sounds.clear();
       worker.send('restart');
       worker.once('loaded', () => worker.send('start'));
 
@@ -267,8 +431,14 @@ const middleware = store => next => (action) => {
       break;
 
     case 'TS::SUBMIT':
+      stopTimedInput();
       last_input = action.input;
-      worker.send('input', action.input);
+
+      if (action.key) {
+        worker.send('input_key', { input: action.input, key: action.key });
+      } else {
+        worker.send('input', action.input);
+      }
 
       next(action);
       break;
@@ -6,6 +6,7 @@ const initialTranscript = {
   undos: [],
   history: [],
   header: { left: '', right: '' },
+  upper: [],
   quit: false,
 };
 
@@ -13,7 +14,7 @@ function transcript(state = initialTranscript, action) {
   switch (action.type) {
     case 'TS::TEXT':
       return Object.assign({}, state, {
-        moves: [...state.moves, { text: action.text, input: '' }],
+        moves: [...state.moves, { text: action.text, input: action.input || '' }],
         undos: [],
       });
 
@@ -30,6 +31,13 @@ function transcript(state = initialTranscript, action) {
         history,
       });
 
+    case 'TS::ABORT':
+      return Object.assign({}, state, {
+        moves: [...state.moves.slice(0, -1), Object.assign({}, state.moves.slice(-1)[0], {
+          input: action.input,
+        })],
+      });
+
     case 'TS::UNDO':
       return Object.assign({}, state, {
         undos: [...state.undos, state.moves.pop()],
@@ -49,6 +57,11 @@ function transcript(state = initialTranscript, action) {
         header: { left, right },
       });
 
+    case 'TS::WINDOW':
+      return Object.assign({}, state, {
+        upper: JSON.parse(action.data),
+      });
+
     case 'TS::STOP':
     case 'TS::RESTART':
       return Object.assign({}, initialTranscript);
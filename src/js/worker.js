@@ -3,8 +3,9 @@ import { Wrapper, rust } from 'wasm-ffi';
 // const wasmURL = 'web.wasm';
 const wasmURL = process.env.ENCRUSTEDROOT + "web.wasm"
 
-// hold onto active file in case of restarts
+// hold onto active file (and the screen size) in case of restarts
 let file = null;
+let size = { lines: 0, columns: 0 };
 
 
 function sendWorkerMessage(type, msg) {
@@ -14,13 +15,18 @@ function sendWorkerMessage(type, msg) {
 
 const zmachine = new Wrapper({
   hook: [],
-  create: [null, ['number', 'number']],
+  create: ['bool', ['number', 'number', 'number', 'number']],
   feed: [null, ['string']],
+  feed_key: [null, ['number']],
+  feed_terminated: [null, ['string', 'number']],
+  tick: ['bool', ['string']],
+  sound_finished: [null, ['number']],
   step: ['bool'],
   undo: ['bool'],
   redo: ['bool'],
   get_updates: [],
   restore: [null, ['string']],
+  restore_aux: [null, ['string']],
   load_savestate: [null, ['string']],
   enable_instruction_logs: [null, ['bool']],
   get_object_details: [rust.string, ['number']],
@@ -91,10 +97,13 @@ onmessage = (ev) => {
     instantiate()
       .then(() => {
         file = new Uint8Array(ev.data.msg.file);
+        size = ev.data.msg.size || size;
         const file_ptr = zmachine.utils.writeArray(file);
 
-        zmachine.create(file_ptr, file.length);
-        sendWorkerMessage('loaded');
+        // (if the file isn't a game, a load_error message says why)
+        if (zmachine.create(file_ptr, file.length, size.lines, size.columns)) {
+          sendWorkerMessage('loaded');
+        }
       })
       .catch(err => setTimeout(() => {
         console.log('Error starting wasm: ', err, err.stack);
@@ -108,8 +117,9 @@ onmessage = (ev) => {
   if (ev.data.type === 'restart') {
     const file_ptr = zmachine.utils.writeArray(file);
 
-    zmachine.create(file_ptr, file.length);
-    sendWorkerMessage('loaded');
+    if (zmachine.create(file_ptr, file.length, size.lines, size.columns)) {
+      sendWorkerMessage('loaded');
+    }
   }
 
   if (ev.data.type === 'input') {
@@ -117,11 +127,40 @@ onmessage = (ev) => {
     step();
   }
 
+  // line input ended by one of the game's terminating keys instead of enter
+  if (ev.data.type === 'input_key') {
+    zmachine.feed_terminated(ev.data.msg.input, ev.data.msg.key);
+    step();
+  }
+
+  if (ev.data.type === 'key') {
+    zmachine.feed_key(ev.data.msg);
+    step();
+  }
+
+  if (ev.data.type === 'tick') {
+    const aborted = zmachine.tick(ev.data.msg);
+
+    if (aborted) {
+      sendWorkerMessage('aborted');
+      step();
+    }
+  }
+
+  if (ev.data.type === 'sound_finished') {
+    zmachine.sound_finished(ev.data.msg);
+  }
+
   if (ev.data.type === 'restore') {
     zmachine.restore(ev.data.msg);
     step();
   }
 
+  if (ev.data.type === 'restore_aux') {
+    zmachine.restore_aux(ev.data.msg);
+    step();
+  }
+
   if (ev.data.type === 'load_savestate') {
     zmachine.load_savestate(ev.data.msg);
     step();
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use iff;
+
+// What a resource gets used for, from its entry in the resource index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Usage {
+    Picture,
+    Sound,
+    Data,
+    Exec,
+}
+
+#[derive(Debug)]
+pub struct Resource {
+    pub usage: Usage,
+    pub number: u32,
+    pub kind: String, // chunk type, like "ZCOD", "PNG " or "OGGV"
+    pub data: Vec<u8>,
+}
+
+// A Blorb file bundles a game together with its pictures, sounds & metadata.
+// Everything is listed in a resource index (RIdx) by usage & number.
+#[derive(Debug, Default)]
+pub struct Blorb {
+    pub resources: Vec<Resource>,
+    pub metadata: Option<String>,   // IFmd chunk, iFiction xml
+    pub frontispiece: Option<u32>, // Fspc chunk, a picture number
+    pub loops: HashMap<u32, u32>,  // Loop chunk, sound repeats for v3 games
+}
+
+impl Blorb {
+    pub fn is_blorb(data: &[u8]) -> bool {
+        data.len() >= 12 && &data[0..4] == b"FORM" && &data[8..12] == b"IFRS"
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Blorb, String> {
+        let chunks = iff::read_form(data, "IFRS").ok_or("Invalid blorb file")?;
+        let mut blorb = Blorb::default();
+
+        for &(ref id, _, body) in &chunks {
+            if id == "RIdx" {
+                blorb.read_ridx_body(data, body)?;
+            } else if id == "IFmd" {
+                blorb.metadata = Some(String::from_utf8_lossy(body).into_owned());
+            } else if id == "Fspc" {
+                blorb.frontispiece = iff::read_u32(body, 0);
+            } else if id == "Loop" {
+                // pairs of sound number & repeat count (0 = forever)
+                for pair in body.chunks(8) {
+                    if let (Some(number), Some(value)) =
+                        (iff::read_u32(pair, 0), iff::read_u32(pair, 4))
+                    {
+                        blorb.loops.insert(number, value);
+                    }
+                }
+            }
+        }
+
+        Ok(blorb)
+    }
+
+    // Each index entry is a usage, a resource number and the offset of the
+    // resource's chunk from the start of the file (12 bytes each)
+    fn read_ridx_body(&mut self, data: &[u8], body: &[u8]) -> Result<(), String> {
+        let count = iff::read_u32(body, 0).ok_or("Invalid blorb resource index")?;
+
+        for n in 0..count as usize {
+            let entry = 4 + n * 12;
+            let usage = match body.get(entry..entry + 4) {
+                Some(b"Pict") => Usage::Picture,
+                Some(b"Snd ") => Usage::Sound,
+                Some(b"Data") => Usage::Data,
+                Some(b"Exec") => Usage::Exec,
+                Some(_) => continue, // unknown usage, skip it
+                None => return Err("Blorb resource index is truncated".to_string()),
+            };
+
+            let number = iff::read_u32(body, entry + 4).unwrap_or(0);
+            let start = iff::read_u32(body, entry + 8).unwrap_or(0) as usize;
+
+            let (id, length, chunk) = data
+                .get(start..)
+                .and_then(iff::read_chunk)
+                .ok_or_else(|| format!("Blorb resource {} is missing", number))?;
+
+            // AIFF sounds are FORM chunks themselves, and they're only
+            // playable with the FORM header, so those keep the whole chunk
+            let (kind, data) = if id == "FORM" && chunk.len() >= 4 {
+                let end = (start + length).min(data.len());
+                let kind = String::from_utf8_lossy(&chunk[0..4]).into_owned();
+                (kind, data[start..end].to_vec())
+            } else {
+                (id, chunk.to_vec())
+            };
+
+            self.resources.push(Resource {
+                usage,
+                number,
+                kind,
+                data,
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, usage: Usage, number: u32) -> Option<&Resource> {
+        self.resources
+            .iter()
+            .find(|res| res.usage == usage && res.number == number)
+    }
+}
+
+// Story files can either be plain zcode or a blorb with the zcode inside
+// (executable resource 0). Returns the zcode, and any other resources.
+pub fn load_story(data: Vec<u8>) -> Result<(Vec<u8>, Option<Blorb>), String> {
+    if !Blorb::is_blorb(&data) {
+        return Ok((data, None));
+    }
+
+    let mut blorb = Blorb::from_bytes(&data)?;
+
+    let index = blorb
+        .resources
+        .iter()
+        .position(|res| res.usage == Usage::Exec && res.number == 0)
+        .ok_or("Blorb file doesn't contain a game")?;
+
+    let story = blorb.resources.remove(index);
+
+    if story.kind != "ZCOD" {
+        return Err(format!(
+            "Blorb contains a \"{}\" game, not zcode",
+            story.kind.trim()
+        ));
+    }
+
+    Ok((story.data, Some(blorb)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a blorb with a single resource, `chunk` (written out as a whole chunk)
+    fn make_blorb(usage: &[u8], number: u32, chunk: &[u8]) -> Vec<u8> {
+        let mut ridx = Vec::new();
+        ridx.extend(&[0, 0, 0, 1]);
+        ridx.extend(usage);
+        ridx.extend(&number.to_be_bytes());
+        ridx.extend(&36u32.to_be_bytes()); // FORM header (12) + RIdx (8 + 16)
+
+        let mut body = b"IFRS".to_vec();
+        iff::write_chunk(&mut body, "RIdx", &ridx);
+        body.extend(chunk);
+
+        let mut data = Vec::new();
+        iff::write_chunk(&mut data, "FORM", &body);
+        data
+    }
+
+    #[test]
+    fn keeps_aiff_header() {
+        let mut aiff = Vec::new();
+        iff::write_chunk(&mut aiff, "FORM", b"AIFFCOMM\0\0\0\x02\0\x01");
+
+        let blorb = Blorb::from_bytes(&make_blorb(b"Snd ", 3, &aiff)).unwrap();
+        let sound = blorb.get(Usage::Sound, 3).unwrap();
+
+        assert_eq!(sound.kind, "AIFF");
+        assert_eq!(&sound.data[0..4], b"FORM");
+        assert_eq!(sound.data, aiff);
+    }
+
+    #[test]
+    fn reads_other_resources_as_bodies() {
+        let mut ogg = Vec::new();
+        iff::write_chunk(&mut ogg, "OGGV", b"OggS");
+
+        let blorb = Blorb::from_bytes(&make_blorb(b"Snd ", 4, &ogg)).unwrap();
+        let sound = blorb.get(Usage::Sound, 4).unwrap();
+
+        assert_eq!(sound.kind, "OGGV");
+        assert_eq!(sound.data, b"OggS");
+    }
+}
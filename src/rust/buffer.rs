@@ -1,5 +1,6 @@
 #![allow(dead_code)]
 
+use std::cell::Cell;
 
 #[derive(Debug)]
 pub struct Reader<'a> {
@@ -55,42 +56,73 @@ impl<'a> Writer<'a> {
     }
 }
 
+// Memory that's out of bounds reads as 0 (and ignores writes) instead of
+// panicking. The first bad address gets remembered as a fault, so whoever's
+// running instructions can check for it afterwards with `take_fault`.
 #[derive(Debug)]
 pub struct Buffer {
     buf: Vec<u8>,
+    fault: Cell<Option<usize>>,
 }
 
 impl Buffer {
     pub fn new(buf: Vec<u8>) -> Buffer {
-        Buffer { buf }
+        Buffer {
+            buf,
+            fault: Cell::new(None),
+        }
     }
 
     pub fn len(&self) -> usize {
         self.buf.len()
     }
 
+    fn set_fault(&self, location: usize) {
+        if self.fault.get().is_none() {
+            self.fault.set(Some(location));
+        }
+    }
+
+    pub fn take_fault(&self) -> Option<usize> {
+        self.fault.take()
+    }
+
+    pub fn has_fault(&self) -> bool {
+        self.fault.get().is_some()
+    }
+
     pub fn read_byte(&self, location: usize) -> u8 {
-        self.buf[location]
+        match self.buf.get(location) {
+            Some(&byte) => byte,
+            None => {
+                self.set_fault(location);
+                0
+            }
+        }
     }
 
     pub fn read_word(&self, location: usize) -> u16 {
-        (u16::from(self.buf[location]) << 8) + u16::from(self.buf[location + 1])
+        (u16::from(self.read_byte(location)) << 8) + u16::from(self.read_byte(location + 1))
     }
 
     pub fn write_byte(&mut self, location: usize, value: u8) {
-        self.buf[location] = value;
+        match self.buf.get_mut(location) {
+            Some(byte) => *byte = value,
+            None => self.set_fault(location),
+        }
     }
 
     pub fn write_word(&mut self, location: usize, value: u16) {
         let top = ((value & 0xFF00) >> 8) as u8;
         let bottom = (value & 0x00FF) as u8;
 
-        self.buf[location] = top;
-        self.buf[location + 1] = bottom;
+        self.write_byte(location, top);
+        self.write_byte(location + 1, bottom);
     }
 
+    // (anything past the end of memory gets cut off)
     pub fn read(&self, location: usize, length: usize) -> &[u8] {
-        &self.buf[location..location + length]
+        self.slice(location, location + length)
     }
 
     pub fn write(&mut self, location: usize, buf: &[u8]) {
@@ -100,7 +132,14 @@ impl Buffer {
     }
 
     pub fn slice(&self, start: usize, end: usize) -> &[u8] {
-        &self.buf[start..end]
+        if start > end {
+            self.set_fault(start);
+        } else if end > self.buf.len() {
+            self.set_fault(start.max(self.buf.len()));
+        }
+
+        let end = end.min(self.buf.len());
+        &self.buf[start.min(end)..end]
     }
 
     pub fn get_reader(&self, cursor: usize) -> Reader {
@@ -0,0 +1,64 @@
+use std::fmt;
+
+use instruction::Opcode;
+
+// What went wrong when the zmachine couldn't carry on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cause {
+    OutOfBounds(usize),     // read or write past the end of memory
+    StackUnderflow,         // popped or peeked an empty stack
+    NoFrames,               // returned from the last routine
+    BadLocal(u8),           // local variable the routine doesn't have
+    BadFrame(u16),          // throw to a frame that doesn't exist
+    UnknownOpcode(u16),     // not an opcode at all
+    Unimplemented(String),  // opcode we don't handle (with those operands)
+    StreamOverflow,         // output stream 3 nested too deep
+    InvalidSave(String),    // save data that can't be restored
+    NotPaused,              // js resumed an instruction that isn't waiting
+    Violation(String),      // something the spec doesn't allow (when fatal)
+    Interrupt(Box<ZmachineError>), // error inside an interrupt routine
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Cause::OutOfBounds(addr) => write!(f, "memory access out of bounds at {:#06x}", addr),
+            Cause::StackUnderflow => write!(f, "stack underflow"),
+            Cause::NoFrames => write!(f, "can't return, no routine to return to"),
+            Cause::BadLocal(index) => write!(f, "local variable {} doesn't exist", index),
+            Cause::BadFrame(frame) => write!(f, "can't throw to frame {}", frame),
+            Cause::UnknownOpcode(num) => write!(f, "opcode not found: {}", num),
+            Cause::Unimplemented(ref name) => write!(f, "opcode not implemented: {}", name),
+            Cause::StreamOverflow => write!(f, "output stream 3 nested more than 16 levels deep"),
+            Cause::InvalidSave(ref msg) => write!(f, "invalid save: {}", msg),
+            Cause::NotPaused => write!(f, "no paused instruction to resume"),
+            Cause::Violation(ref msg) => write!(f, "{}", msg),
+            Cause::Interrupt(ref err) => write!(f, "interrupt routine failed: {}", err),
+        }
+    }
+}
+
+// An error that stopped the zmachine, along with where it happened. The
+// opcode is missing if the instruction couldn't be decoded (or there wasn't
+// one, like restoring a save).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZmachineError {
+    pub pc: usize,
+    pub opcode: Option<Opcode>,
+    pub cause: Cause,
+}
+
+impl ZmachineError {
+    pub fn new(pc: usize, opcode: Option<Opcode>, cause: Cause) -> ZmachineError {
+        ZmachineError { pc, opcode, cause }
+    }
+}
+
+impl fmt::Display for ZmachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.opcode {
+            Some(opcode) => write!(f, "{} ({:?} at pc {:#06x})", self.cause, opcode, self.pc),
+            None => write!(f, "{} (at pc {:#06x})", self.cause, self.pc),
+        }
+    }
+}
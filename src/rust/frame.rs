@@ -1,6 +1,8 @@
 use std::fmt;
 
-#[derive(Debug)]
+use error::Cause;
+
+#[derive(Debug, Clone)]
 pub struct Frame {
     stack: Vec<u16>,
     locals: Vec<u16>,
@@ -93,36 +95,33 @@ impl Frame {
         }
     }
 
-    pub fn read_local(&self, index: u8) -> u16 {
-        let index = index as usize;
-
-        if index > self.locals.len() {
-            panic!("Trying to read out of bounds local @: {}", index);
-        }
-
-        self.locals[index]
+    pub fn read_local(&self, index: u8) -> Result<u16, Cause> {
+        self.locals
+            .get(index as usize)
+            .cloned()
+            .ok_or(Cause::BadLocal(index))
     }
 
-    pub fn write_local(&mut self, index: u8, value: u16) {
-        let index = index as usize;
-
-        if index > self.locals.len() {
-            panic!("Trying to write out of bounds local @: {}", index);
-        }
+    pub fn write_local(&mut self, index: u8, value: u16) -> Result<(), Cause> {
+        let local = self
+            .locals
+            .get_mut(index as usize)
+            .ok_or(Cause::BadLocal(index))?;
 
-        self.locals[index] = value;
+        *local = value;
+        Ok(())
     }
 
     pub fn stack_push(&mut self, value: u16) {
         self.stack.push(value);
     }
 
-    pub fn stack_pop(&mut self) -> u16 {
-        self.stack.pop().expect("Can't pop off an empty stack!")
+    pub fn stack_pop(&mut self) -> Result<u16, Cause> {
+        self.stack.pop().ok_or(Cause::StackUnderflow)
     }
 
-    pub fn stack_peek(&self) -> u16 {
-        *self.stack.last().expect("Can't peek on an empty stack!")
+    pub fn stack_peek(&self) -> Result<u16, Cause> {
+        self.stack.last().cloned().ok_or(Cause::StackUnderflow)
     }
 
     pub fn to_string(&self) -> String {
@@ -157,8 +156,9 @@ impl Frame {
         bytes.push(((self.resume & 0x00_FF00) >> 8) as u8);
         bytes.push((self.resume & 0x00_00FF) as u8);
 
-        let mut flags = self.locals.len() as u8; // 0b0000vvvv
-        if self.store.is_some() {
+        // 0b000pvvvv (p is set when the result gets discarded)
+        let mut flags = self.locals.len() as u8;
+        if self.store.is_none() {
             flags += 0b0001_0000;
         }
 
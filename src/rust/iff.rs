@@ -0,0 +1,83 @@
+// Helpers for IFF files, which both Quetzal saves & Blorb files are built on.
+// A chunk is a 4 char id, a 4 byte (BE) body length and then the body, padded
+// to an even length. A FORM chunk's body is a 4 char type then more chunks.
+
+// Reads the chunk at the start of `data` as (id, total chunk length, body).
+// Returns None if the data is too short to hold the whole chunk.
+pub fn read_chunk(data: &[u8]) -> Option<(String, usize, &[u8])> {
+    if data.len() < 8 {
+        return None;
+    }
+
+    let header = String::from_utf8_lossy(&data[0..4]).into_owned();
+
+    let mut body_length = 0;
+    body_length += (data[4] as usize) << 24;
+    body_length += (data[5] as usize) << 16;
+    body_length += (data[6] as usize) << 8;
+    body_length += data[7] as usize;
+
+    let body = data.get(8..(8 + body_length))?;
+
+    // chunks get padded with an empty 0 byte if they have an odd length
+    let mut chunk_length = 8 + body_length;
+    if chunk_length % 2 != 0 {
+        chunk_length += 1;
+    }
+
+    Some((header, chunk_length, body))
+}
+
+// Reads a FORM chunk of the given type, returning its inner chunks along with
+// each one's offset from the start of `data`.
+pub fn read_form<'a>(data: &'a [u8], form_type: &str) -> Option<Vec<(String, usize, &'a [u8])>> {
+    let (header, _, form_body) = read_chunk(data)?;
+
+    if header != "FORM" || form_body.get(0..4)? != form_type.as_bytes() {
+        return None;
+    }
+
+    let mut chunks = Vec::new();
+    let mut offset = 12; // FORM id + length + type
+
+    while offset < 8 + form_body.len() {
+        let (id, length, body) = read_chunk(&data[offset..])?;
+        chunks.push((id, offset, body));
+        offset += length;
+    }
+
+    Some(chunks)
+}
+
+pub fn write_chunk(bytes: &mut Vec<u8>, header: &str, body: &[u8]) {
+    // 4 bytes for the header string
+    bytes.extend(&header.as_bytes()[0..4]);
+
+    // 4 bytes for the length (BE)
+    let length = body.len();
+    bytes.push(((length & 0xFF00_0000) >> 24) as u8);
+    bytes.push(((length & 0x00FF_0000) >> 16) as u8);
+    bytes.push(((length & 0x0000_FF00) >> 8) as u8);
+    bytes.push((length & 0x0000_00FF) as u8);
+
+    // + body
+    bytes.extend(body);
+
+    // If the body length is odd, add a padding byte. This extra byte is
+    // *not* included in the length marker above.
+    if body.len() % 2 != 0 {
+        bytes.push(0);
+    }
+}
+
+// reads a 4 byte (BE) number, as used throughout IFF chunks
+pub fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+
+    Some(
+        (u32::from(bytes[0]) << 24)
+            | (u32::from(bytes[1]) << 16)
+            | (u32::from(bytes[2]) << 8)
+            | u32::from(bytes[3]),
+    )
+}
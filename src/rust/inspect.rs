@@ -0,0 +1,351 @@
+// Looks inside Quetzal save files, for when a restore doesn't do what it
+// should. Changed bytes in dynamic memory get decoded using the story's
+// header: globals, the object table (attributes & the object tree) and
+// everything else.
+use std::fmt::Write;
+
+use quetzal::QuetzalSave;
+use zmachine::{Layout, Zmachine};
+
+// The globals and object table are found the same way the zmachine finds
+// them, this just reads them out of a save's memory
+impl Layout {
+    fn is_global(&self, addr: usize) -> bool {
+        addr >= self.globals && addr < self.globals + 240 * 2
+    }
+
+    fn is_object_table(&self, addr: usize) -> bool {
+        addr >= self.prop_defaults && addr < self.objects + self.object_count * self.obj_size
+    }
+
+    fn object(&self, memory: &[u8], num: usize) -> Object {
+        let start = self.objects + (num - 1) * self.obj_size;
+        let entry = memory.get(start..start + self.obj_size).unwrap_or(&[]);
+        let mut object = Object::default();
+
+        if entry.len() != self.obj_size {
+            return object;
+        }
+
+        object.attributes = entry[..self.attr_width].to_vec();
+
+        let links = &entry[self.attr_width..];
+        if self.small_objects {
+            object.parent = u16::from(links[0]);
+            object.sibling = u16::from(links[1]);
+            object.child = u16::from(links[2]);
+        } else {
+            object.parent = read_word(links, 0);
+            object.sibling = read_word(links, 2);
+            object.child = read_word(links, 4);
+        }
+
+        object
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Object {
+    attributes: Vec<u8>,
+    parent: u16,
+    sibling: u16,
+    child: u16,
+}
+
+impl Object {
+    // attribute 0 is the top bit of the first byte
+    fn has_attr(&self, attr: usize) -> bool {
+        let byte = self.attributes.get(attr / 8).cloned().unwrap_or(0);
+        byte & (0b1000_0000 >> (attr % 8)) != 0
+    }
+}
+
+fn read_word(memory: &[u8], addr: usize) -> u16 {
+    match memory.get(addr..addr + 2) {
+        Some(bytes) => u16::from(bytes[0]) << 8 | u16::from(bytes[1]),
+        None => 0,
+    }
+}
+
+// Prints the IFhd fields, the call stack, any other chunks and what's
+// different from the game's starting memory
+pub fn inspect(zvm: &Zmachine, original: &[u8], save: &QuetzalSave) -> String {
+    let mut out = String::new();
+
+    writeln!(out, "Release:  {}", save.release).unwrap();
+    writeln!(out, "Serial:   {}", String::from_utf8_lossy(&save.serial)).unwrap();
+    writeln!(out, "Checksum: {:#06x}", save.chksum).unwrap();
+    writeln!(out, "PC:       {:#06x}", save.pc).unwrap();
+
+    writeln!(out, "\nStack frames ({}):", save.frames.len()).unwrap();
+    for (i, frame) in save.frames.iter().enumerate() {
+        writeln!(out, "  {:2}: {}", i, frame).unwrap();
+    }
+
+    if !save.chunks.is_empty() {
+        writeln!(out, "\nOther chunks:").unwrap();
+        for (id, body) in &save.chunks {
+            writeln!(out, "  \"{}\" ({} bytes)", id, body.len()).unwrap();
+        }
+    }
+
+    writeln!(out, "\nChanges from the start of the game:").unwrap();
+    write_changes(&mut out, zvm, original, &save.memory);
+
+    out
+}
+
+// Compares two saves of the same game, `before` being the earlier one
+pub fn diff(zvm: &Zmachine, before: &QuetzalSave, after: &QuetzalSave) -> Result<String, String> {
+    if after
+        .check_story(before.release, &before.serial, before.chksum)
+        .is_err()
+    {
+        return Err("the saves are from different games".to_string());
+    }
+
+    let mut out = String::new();
+
+    writeln!(out, "PC:           {:#06x} -> {:#06x}", before.pc, after.pc).unwrap();
+    writeln!(
+        out,
+        "Stack frames: {} -> {}",
+        before.frames.len(),
+        after.frames.len()
+    )
+    .unwrap();
+
+    writeln!(out, "\nChanges between the saves:").unwrap();
+    write_changes(&mut out, zvm, &before.memory, &after.memory);
+
+    Ok(out)
+}
+
+fn write_changes(out: &mut String, zvm: &Zmachine, before: &[u8], after: &[u8]) {
+    let layout = zvm.layout();
+    let changed: Vec<usize> = (0..before.len().min(after.len()))
+        .filter(|&addr| before[addr] != after[addr])
+        .collect();
+
+    if changed.is_empty() {
+        writeln!(out, "  (nothing)").unwrap();
+        return;
+    }
+
+    // Globals: each one is a word, numbered from 0 (variable 0x10)
+    let mut globals: Vec<usize> = changed
+        .iter()
+        .filter(|&&addr| layout.is_global(addr))
+        .map(|addr| (addr - layout.globals) / 2)
+        .collect();
+    globals.dedup();
+
+    if !globals.is_empty() {
+        writeln!(out, "\n  Globals:").unwrap();
+    }
+
+    for num in globals {
+        let addr = layout.globals + num * 2;
+        writeln!(
+            out,
+            "    G{:02x} (var {:#04x} @ {:#06x}): {:#06x} -> {:#06x}",
+            num,
+            num + 0x10,
+            addr,
+            read_word(before, addr),
+            read_word(after, addr)
+        )
+        .unwrap();
+    }
+
+    // Object table: property defaults, then each object's attributes and
+    // its place in the object tree
+    let table: Vec<usize> = changed
+        .iter()
+        .cloned()
+        .filter(|&addr| layout.is_object_table(addr))
+        .collect();
+
+    if !table.is_empty() {
+        writeln!(out, "\n  Object table:").unwrap();
+    }
+
+    let mut defaults: Vec<usize> = table
+        .iter()
+        .filter(|&&addr| addr < layout.objects)
+        .map(|addr| (addr - layout.prop_defaults) / 2)
+        .collect();
+    defaults.dedup();
+
+    for index in defaults {
+        let addr = layout.prop_defaults + index * 2;
+        writeln!(
+            out,
+            "    default for property {}: {:#06x} -> {:#06x}",
+            index + 1,
+            read_word(before, addr),
+            read_word(after, addr)
+        )
+        .unwrap();
+    }
+
+    let mut objects: Vec<usize> = table
+        .iter()
+        .filter(|&&addr| addr >= layout.objects)
+        .map(|addr| (addr - layout.objects) / layout.obj_size + 1)
+        .collect();
+    objects.dedup();
+
+    for num in objects {
+        let old = layout.object(before, num);
+        let new = layout.object(after, num);
+        let mut changes = Vec::new();
+
+        if old.parent != new.parent {
+            changes.push(format!(
+                "parent {} -> {}",
+                object_label(zvm, old.parent),
+                object_label(zvm, new.parent)
+            ));
+        }
+
+        let attributes = (0..layout.attr_width * 8)
+            .filter(|&attr| old.has_attr(attr) != new.has_attr(attr))
+            .map(|attr| format!("{}{}", if new.has_attr(attr) { "+" } else { "-" }, attr))
+            .collect::<Vec<_>>();
+
+        if !attributes.is_empty() {
+            changes.push(format!("attributes {}", attributes.join(" ")));
+        }
+
+        if old.sibling != new.sibling {
+            changes.push(format!("sibling #{} -> #{}", old.sibling, new.sibling));
+        }
+
+        if old.child != new.child {
+            changes.push(format!("child #{} -> #{}", old.child, new.child));
+        }
+
+        // (otherwise it's the address of the object's properties)
+        if changes.is_empty() {
+            changes.push("property table moved".to_string());
+        }
+
+        writeln!(
+            out,
+            "    {}: {}",
+            object_label(zvm, num as u16),
+            changes.join(", ")
+        )
+        .unwrap();
+    }
+
+    // Everything else (the header, arrays, property values...) as runs of
+    // changed bytes
+    let other: Vec<usize> = changed
+        .iter()
+        .cloned()
+        .filter(|&addr| !layout.is_global(addr) && !layout.is_object_table(addr))
+        .collect();
+
+    if !other.is_empty() {
+        writeln!(out, "\n  Other memory:").unwrap();
+    }
+
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for addr in other {
+        match runs.last_mut() {
+            Some(run) if run.1 == addr => run.1 += 1,
+            _ => runs.push((addr, addr + 1)),
+        }
+    }
+
+    for (start, end) in runs {
+        let hex = |memory: &[u8]| {
+            memory[start..end]
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        if end - start <= 8 {
+            writeln!(out, "    {:#06x}: {} -> {}", start, hex(before), hex(after)).unwrap();
+        } else {
+            writeln!(
+                out,
+                "    {:#06x}-{:#06x}: {} bytes changed",
+                start,
+                end - 1,
+                end - start
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn object_label(zvm: &Zmachine, num: u16) -> String {
+    if num == 0 {
+        return "#0".to_string();
+    }
+
+    let name = zvm.object_name(num);
+    if name.is_empty() {
+        format!("#{}", num)
+    } else {
+        format!("#{} \"{}\"", num, name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use options::Options;
+    use traits::UI;
+    use ui_terminal::TerminalUI;
+
+    fn tests_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+    }
+
+    fn minizork() -> (Zmachine, Vec<u8>, u8) {
+        let data = fs::read(tests_dir().join("minizork.z3")).unwrap();
+        let static_start = read_word(&data, 0x0E) as usize;
+        let original = data[..static_start].to_vec();
+        let version = data[0];
+
+        (Zmachine::new(data, TerminalUI::new(), Options::default()), original, version)
+    }
+
+    fn save(name: &str, original: &[u8], version: u8) -> QuetzalSave {
+        let data = fs::read(tests_dir().join("quetzal").join(name)).unwrap();
+        QuetzalSave::from_bytes(&data, original, version).unwrap()
+    }
+
+    // umem.sav is the same game as cmem.sav, one "north" later
+    #[test]
+    fn diffs_a_move() {
+        let (zvm, original, version) = minizork();
+        let before = save("minizork.cmem.sav", &original, version);
+        let after = save("minizork.umem.sav", &original, version);
+        let out = diff(&zvm, &before, &after).unwrap();
+
+        assert!(out.contains("G00 (var 0x10 @ 0x02b4): 0x002e -> 0x0060"));
+        assert!(out.contains(
+            "#30 \"you\": parent #46 \"West of House\" -> #96 \"North of House\", sibling #82 -> #0"
+        ));
+        assert!(out.contains("#96 \"North of House\": attributes +13, child #0 -> #30"));
+    }
+
+    #[test]
+    fn diffs_nothing_against_itself() {
+        let (zvm, original, version) = minizork();
+        let save = save("minizork.cmem.sav", &original, version);
+        let out = diff(&zvm, &save, &save).unwrap();
+
+        assert!(out.contains("Changes between the saves:\n  (nothing)"));
+    }
+}
@@ -20,60 +20,226 @@ use std::io::prelude::*;
 use std::path::Path;
 use std::process;
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
+mod blorb;
 mod buffer;
+mod error;
 mod frame;
+mod iff;
+mod inspect;
 mod instruction;
 mod options;
 mod quetzal;
 mod traits;
 mod ui_terminal;
 mod zmachine;
+mod zscii;
 
-use options::Options;
+use options::{ErrorLevel, Options};
+use quetzal::QuetzalSave;
 use traits::UI;
 use ui_terminal::TerminalUI;
 use zmachine::Zmachine;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// Reads a story file (plain zcode or a blorb), exiting if it can't be loaded
+fn read_story(path: &Path) -> (Vec<u8>, Option<blorb::Blorb>) {
+    if !path.is_file() {
+        println!(
+            "\nCouldn't find game file: \n   {}\n",
+            path.to_string_lossy()
+        );
+        process::exit(1);
+    }
+
+    let mut data = Vec::new();
+    let mut file = File::open(path).expect("Error opening file");
+    file.read_to_end(&mut data).expect("Error reading file");
+
+    // .zblorb files have the game inside them, along with pictures & sounds
+    match blorb::load_story(data) {
+        Ok(story) => story,
+        Err(msg) => {
+            println!("\nCouldn't load \"{}\":\n   {}\n", path.to_string_lossy(), msg);
+            process::exit(1);
+        }
+    }
+}
+
+// `encrusted inspect <FILE> <SAVE> [OTHER]` prints what's in a save file, or
+// what changed between two saves of the same game
+fn inspect_saves(matches: &ArgMatches) {
+    let path = Path::new(matches.value_of("FILE").unwrap());
+    let (data, _) = read_story(path);
+
+    if data.len() < 0x40 {
+        println!("\n\"{}\" isn't a valid game file\n", path.to_string_lossy());
+        process::exit(1);
+    }
+
+    let word = |addr: usize| u16::from(data[addr]) << 8 | u16::from(data[addr + 1]);
+    let (release, chksum) = (word(0x02), word(0x1C));
+    let serial = data[0x12..0x18].to_vec();
+    let static_start = (word(0x0E) as usize).min(data.len());
+    let original = data[..static_start].to_vec();
+
+    let read_save = |name: &str| {
+        let mut save_data = Vec::new();
+        let result = File::open(name)
+            .and_then(|mut file| file.read_to_end(&mut save_data))
+            .map_err(|err| err.to_string())
+            .and_then(|_| QuetzalSave::from_bytes(&save_data, &original, data[0]));
+
+        match result {
+            Ok(save) => save,
+            Err(msg) => {
+                println!("\nCouldn't read save file \"{}\":\n   {}\n", name, msg);
+                process::exit(1);
+            }
+        }
+    };
+
+    let save = read_save(matches.value_of("SAVE").unwrap());
+    let other = matches.value_of("OTHER").map(read_save);
+
+    if let Err(msg) = save.check_story(release, &serial, chksum) {
+        println!("Warning: {}\n", msg);
+    }
+
+    // used to look up object names and where the object table is
+    let zvm = Zmachine::new(data, TerminalUI::new(), Options::default());
+
+    match other {
+        Some(other) => match inspect::diff(&zvm, &save, &other) {
+            Ok(out) => print!("{}", out),
+            Err(msg) => {
+                println!("\nCan't compare those saves:\n   {}\n", msg);
+                process::exit(1);
+            }
+        },
+        None => print!("{}", inspect::inspect(&zvm, &original, &save)),
+    }
+}
+
 fn main() {
     let matches = App::new("encrusted")
         .version(VERSION)
         .about("A zmachine interpreter")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .setting(AppSettings::ArgsNegateSubcommands)
         .arg(
             Arg::with_name("FILE")
                 .help("Sets the story file to run")
                 .required(true),
         )
+        .arg(
+            Arg::with_name("script")
+                .short("s")
+                .long("script")
+                .value_name("COMMANDS")
+                .help("Plays back commands from a file before reading the keyboard")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("errors")
+                .short("e")
+                .long("errors")
+                .value_name("LEVEL")
+                .help("What to do when a game breaks the spec rules (defaults to \"once\")")
+                .possible_values(&["fatal", "once", "always", "ignore"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("umem")
+                .long("umem")
+                .help("Writes save files with uncompressed memory (UMem)"),
+        )
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about("Shows what's in a save file, or compares two saves of the same game")
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("The story file the save is for")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("SAVE")
+                        .help("The save file to look at")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("OTHER")
+                        .help("A later save to compare it with"),
+                ),
+        )
         .get_matches();
 
+    if let Some(matches) = matches.subcommand_matches("inspect") {
+        inspect_saves(matches);
+        return;
+    }
+
     let path = Path::new(matches.value_of("FILE").unwrap());
+    let (data, resources) = read_story(path);
 
-    if !path.is_file() {
-        println!(
-            "\nCouldn't find game file: \n   {}\n",
-            path.to_string_lossy()
-        );
-        process::exit(1);
-    }
+    let version = if data.len() < 0x40 { 0 } else { data[0] };
 
-    let mut data = Vec::new();
-    let mut file = File::open(path).expect("Error opening file");
-    file.read_to_end(&mut data).expect("Error reading file");
+    // v1/v2 text encoding & v6 graphics aren't implemented
+    match version {
+        3..=5 | 7 | 8 => (),
+        1 | 2 | 6 => {
+            println!(
+                "\n\
+                 \"{}\" is a version {} game, which isn't supported yet.\n\
+                 Supported versions are 3, 4, 5, 7 and 8.\n",
+                path.to_string_lossy(),
+                version
+            );
+            process::exit(1);
+        }
+        _ => {
+            println!(
+                "\n\
+                 \"{}\" has an unsupported game version: {}\n\
+                 Is this a valid game file?\n",
+                path.to_string_lossy(),
+                version
+            );
+            process::exit(1);
+        }
+    }
 
-    let version = data[0];
+    // check the story file against its header so corrupted downloads get
+    // noticed up front (games without a checksum just skip this)
+    let length = Zmachine::file_length(&data);
+    let expected = u16::from(data[0x1C]) << 8 | u16::from(data[0x1D]);
+    let checksum = Zmachine::calculate_checksum(&data);
 
-    if version == 0 || version > 8 {
+    if data.len() < length {
         println!(
-            "\n\
-             \"{}\" has an nsupported game version: {}\n\
-             Is this a valid game file?\n",
+            "\nWarning: \"{}\" is truncated ({} of {} bytes).\n",
             path.to_string_lossy(),
-            version
+            data.len(),
+            length
         );
-        process::exit(1);
+    } else if expected != 0 && checksum != expected {
+        println!(
+            "\nWarning: \"{}\" failed its integrity check \
+             (checksum {:#06x}, expected {:#06x}).\n\
+             The file may be corrupt.\n",
+            path.to_string_lossy(),
+            checksum,
+            expected
+        );
+    }
+
+    if let Some(script) = matches.value_of("script") {
+        if !Path::new(script).is_file() {
+            println!("\nCouldn't find command file: \n   {}\n", script);
+            process::exit(1);
+        }
     }
 
     let ui = TerminalUI::new();
@@ -81,11 +247,36 @@ fn main() {
     let mut opts = Options::default();
     opts.save_dir = path.parent().unwrap().to_string_lossy().into_owned();
     opts.save_name = path.file_stem().unwrap().to_string_lossy().into_owned();
+    opts.command_file = matches.value_of("script").unwrap_or("").to_string();
+
+    opts.save_umem = matches.is_present("umem");
+
+    if let Some(level) = matches.value_of("errors").and_then(ErrorLevel::from_name) {
+        opts.error_level = level;
+    }
 
     let rand32 = || rand::random();
     opts.rand_seed = [rand32(), rand32(), rand32(), rand32()];
 
     let mut zvm = Zmachine::new(data, ui, opts);
+    if let Some(resources) = resources {
+        zvm.set_resources(resources);
+    }
 
-    zvm.run();
+    // when the game hits an error, keep its state around for a bug report and
+    // offer to carry on from the turn before
+    while let Err(err) = zvm.run() {
+        println!("\nThe game stopped with an error:\n   {}\n", err);
+
+        if let Some(path) = zvm.save_crash_dump(&err) {
+            println!("Its state was saved to:\n   {}\n", path.to_string_lossy());
+        }
+
+        zvm.ui.print("Undo the last turn and keep playing? [y/n] ");
+        let answer = zvm.ui.get_user_input();
+
+        if !answer.to_lowercase().starts_with('y') || !zvm.undo() {
+            process::exit(1);
+        }
+    }
 }
@@ -19,16 +19,21 @@ extern "C" {
     fn consolelog(ptr: *const c_char);
 }
 
+mod blorb;
 mod buffer;
+mod error;
 mod frame;
+mod iff;
 mod instruction;
 mod options;
 mod quetzal;
 mod traits;
 mod ui_web;
 mod zmachine;
+mod zscii;
 mod panic_hook;
 
+use error::ZmachineError;
 use options::Options;
 use traits::UI;
 use ui_web::WebUI;
@@ -101,25 +106,62 @@ fn push_updates(zvm: &mut Zmachine) {
     }
 }
 
+// Tells js about an error the zmachine stopped on. The game stays paused
+// where it was, so the player can still undo or restore from there.
+fn report_error(zvm: &mut Zmachine, err: &ZmachineError) {
+    let msg = serde_json::to_string(&ErrorMessage {
+        msg: err.to_string(),
+        stack: format!("{:#?}", err),
+    }).unwrap();
+
+    zvm.ui.flush();
+    zvm.ui.message("error", &msg);
+}
+
+#[derive(Serialize)]
+struct ErrorMessage {
+    msg: String,
+    stack: String,
+}
+
+// Returns false (and tells js why) if the file can't be loaded as a game
 #[no_mangle]
-pub fn create(file_ptr: *mut u8, len: usize) {
+pub fn create(file_ptr: *mut u8, len: usize, lines: u16, columns: u16) -> bool {
     ZVM.with(|cell| {
         assert!(!file_ptr.is_null());
 
         let data = unsafe { std::vec::Vec::from_raw_parts(file_ptr, len, len) };
-        let ui = WebUI::new();
+        let (data, resources) = match blorb::load_story(data) {
+            Ok(story) => story,
+            Err(msg) => {
+                WebUI::new().message("load_error", &msg);
+                return false;
+            }
+        };
+
+        let mut ui = WebUI::new();
+        ui.set_screen_size(lines, columns);
+
         let mut opts = Options::default();
         opts.rand_seed = unsafe { [rand(), rand(), rand(), rand()] };
 
-        let zvm = Zmachine::new(data, ui, opts);
+        let mut zvm = Zmachine::new(data, ui, opts);
+        if let Some(resources) = resources {
+            zvm.set_resources(resources);
+        }
+
         *cell.borrow_mut() = Some(zvm);
-    });
+        true
+    })
 }
 
 #[no_mangle]
 pub fn step() -> bool {
     with(|zvm| {
-        let done = zvm.step();
+        let done = zvm.step().unwrap_or_else(|err| {
+            report_error(zvm, &err);
+            false
+        });
 
         zvm.ui.flush();
         push_updates(zvm);
@@ -129,17 +171,83 @@ pub fn step() -> bool {
 
 #[no_mangle]
 pub fn feed(input_ptr: *mut c_char) {
-    with(|zvm| zvm.handle_input(get_string(input_ptr)));
+    with(|zvm| {
+        if let Err(err) = zvm.handle_input(get_string(input_ptr), 13) {
+            report_error(zvm, &err);
+        }
+    });
+}
+
+// line input that was finished with one of the game's terminating keys
+#[no_mangle]
+pub fn feed_terminated(input_ptr: *mut c_char, key: u16) {
+    with(|zvm| {
+        if let Err(err) = zvm.handle_input(get_string(input_ptr), key) {
+            report_error(zvm, &err);
+        }
+    });
+}
+
+#[no_mangle]
+pub fn feed_key(key: u16) {
+    with(|zvm| {
+        if let Err(err) = zvm.handle_key(key) {
+            report_error(zvm, &err);
+        }
+    });
+}
+
+#[no_mangle]
+pub fn tick(input_ptr: *mut c_char) -> bool {
+    with(|zvm| {
+        let aborted = zvm.handle_tick(get_string(input_ptr)).unwrap_or_else(|err| {
+            report_error(zvm, &err);
+            false
+        });
+
+        zvm.ui.flush();
+        push_updates(zvm);
+        aborted
+    })
+}
+
+#[no_mangle]
+pub fn sound_finished(number: u16) {
+    with(|zvm| {
+        if let Err(err) = zvm.handle_sound_finished(number) {
+            report_error(zvm, &err);
+        }
+
+        zvm.ui.flush();
+        push_updates(zvm);
+    })
 }
 
 #[no_mangle]
 pub fn restore(b64_ptr: *mut c_char) {
-    with(|zvm| zvm.restore(&get_string(b64_ptr)));
+    with(|zvm| {
+        if let Err(err) = zvm.restore(&get_string(b64_ptr)) {
+            report_error(zvm, &err);
+        }
+    });
+}
+
+#[no_mangle]
+pub fn restore_aux(b64_ptr: *mut c_char) {
+    with(|zvm| {
+        if let Err(err) = zvm.restore_aux(&get_string(b64_ptr)) {
+            report_error(zvm, &err);
+        }
+    });
 }
 
 #[no_mangle]
 pub fn load_savestate(b64_ptr: *mut c_char) {
-    with(|zvm| zvm.load_savestate(&get_string(b64_ptr)));
+    with(|zvm| {
+        if let Err(err) = zvm.load_savestate(&get_string(b64_ptr)) {
+            report_error(zvm, &err);
+        }
+    });
 }
 
 #[no_mangle]
@@ -1,8 +1,38 @@
+// What happens when a game does something the spec doesn't allow, like using
+// object 0 or dividing by zero. Other than `Fatal` the game keeps going with
+// a fallback value (WarnOnce only warns the first time for each place in the
+// game where it happens).
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorLevel {
+    Fatal,
+    WarnOnce,
+    WarnAlways,
+    Ignore,
+}
+
+impl ErrorLevel {
+    // Terminal UI only
+    #[allow(dead_code)]
+    pub fn from_name(name: &str) -> Option<ErrorLevel> {
+        match name {
+            "fatal" => Some(ErrorLevel::Fatal),
+            "once" => Some(ErrorLevel::WarnOnce),
+            "always" => Some(ErrorLevel::WarnAlways),
+            "ignore" => Some(ErrorLevel::Ignore),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Options {
     pub save_dir: String,
     pub save_name: String,
+    pub command_file: String,
     pub log_instructions: bool,
+    pub error_level: ErrorLevel,
+    pub save_umem: bool, // write saves with uncompressed memory
     pub rand_seed: [u32; 4],
 }
 
@@ -11,7 +41,10 @@ impl Options {
         Options {
             save_dir: String::new(),
             save_name: String::new(),
+            command_file: String::new(),
             log_instructions: false,
+            error_level: ErrorLevel::WarnOnce,
+            save_umem: false,
             rand_seed: [90, 111, 114, 107],
         }
     }
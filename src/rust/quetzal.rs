@@ -1,12 +1,19 @@
 use frame::Frame;
+use iff;
 use std::fmt;
 
+// A save in the Quetzal format (1.4), which other interpreters use too.
+// Chunks other than the ones we need (IntD, ANNO, AUTH, "(c) "...) are kept
+// as they are, so they can be written back out with the next save.
 #[derive(Debug)]
 pub struct QuetzalSave {
     pub pc: usize,
     pub memory: Vec<u8>,
     pub frames: Vec<Frame>,
+    pub release: u16,
+    pub serial: [u8; 6],
     pub chksum: u16,
+    pub chunks: Vec<(String, Vec<u8>)>,
 }
 
 impl QuetzalSave {
@@ -15,16 +22,33 @@ impl QuetzalSave {
             pc: 0,
             memory: Vec::new(),
             frames: Vec::new(),
+            release: 0,
+            serial: [0; 6],
             chksum: 0,
+            chunks: Vec::new(),
         }
     }
 
-    pub fn from_bytes(save_data: &[u8], original_dynamic: &[u8]) -> QuetzalSave {
+    // Reads a save, checking everything against the size of dynamic memory
+    // (the original contents are needed to uncompress CMem chunks). Errors
+    // describe what's wrong with the file, so they can go to the player.
+    pub fn from_bytes(
+        save_data: &[u8],
+        original_dynamic: &[u8],
+        version: u8,
+    ) -> Result<QuetzalSave, String> {
         let mut save = QuetzalSave::empty();
+        let (mut has_ifhd, mut has_stks, mut has_memory) = (false, false, false);
 
-        let (form_header, _, form_body) = QuetzalSave::read_chunk(save_data);
-        if form_header != "FORM" {
-            panic!("Can't find FORM header, bad save file?")
+        if save_data.get(0..4) != Some(&b"FORM"[..]) {
+            return Err("not a save file (no FORM header)".to_string());
+        }
+
+        let (_, _, form_body) = iff::read_chunk(save_data)
+            .ok_or("save file is truncated")?;
+
+        if form_body.get(0..4) != Some(&b"IFZS"[..]) {
+            return Err("not a Quetzal save file (no IFZS header)".to_string());
         }
 
         let chunks = &form_body[4..]; // skip the IFZS string at the start
@@ -32,114 +56,117 @@ impl QuetzalSave {
 
         while offset < chunks.len() {
             let next = &chunks[offset..];
-            let (header, length, body) = QuetzalSave::read_chunk(next);
+            let (header, length, body) = iff::read_chunk(next)
+                .ok_or_else(|| format!("chunk at offset {} is truncated", offset + 12))?;
 
             if header == "IFhd" {
-                save.read_ifhd_body(body);
+                save.read_ifhd_body(body)?;
+                has_ifhd = true;
             } else if header == "Stks" {
-                save.read_stks_body(body);
-            } else if header == "CMem" {
-                save.read_cmem_body(body, original_dynamic);
-            } else if header == "UMem" {
-                save.memory = body.to_vec();
-            }
+                save.read_stks_body(body, version)?;
+                has_stks = true;
+            } else if header == "CMem" || header == "UMem" {
+                // (only the first one counts if there's more than one)
+                if !has_memory && header == "CMem" {
+                    save.read_cmem_body(body, original_dynamic)?;
+                } else if !has_memory {
+                    save.read_umem_body(body, original_dynamic)?;
+                }
 
-            // skip any other unnecessary chunks
-            if save.is_complete() {
-                break;
+                has_memory = true;
+            } else {
+                save.chunks.push((header, body.to_vec()));
             }
 
             offset += length;
         }
 
-        if !save.is_complete() {
-            panic!("Save file doesn't contain all necesary fields");
+        if !has_ifhd {
+            return Err("save file is missing its header (IFhd chunk)".to_string());
         }
 
-        save
-    }
+        if !has_stks {
+            return Err("save file is missing its stack (Stks chunk)".to_string());
+        }
 
-    pub fn make(
-        pc: usize,
-        current: &[u8],
-        original: &[u8],
-        frames: &[Frame],
-        chksum: u16,
-        release: u16,
-        serial: &[u8],
-    ) -> Vec<u8> {
-        let mut save_data = Vec::new();
-        let mut form_body = Vec::from(&b"IFZS"[..]); // Form starts w/ "IFZS"
+        if !has_memory {
+            return Err("save file is missing its memory (CMem/UMem chunk)".to_string());
+        }
 
-        let ifhd_body = QuetzalSave::make_ifhd_body(release, serial, chksum, pc);
-        let stks_body = QuetzalSave::make_stks_body(frames);
-        let cmem_body = QuetzalSave::make_cmem_body(current, original);
+        Ok(save)
+    }
 
-        QuetzalSave::write_chunk(&mut form_body, "IFhd", &ifhd_body[..]);
-        QuetzalSave::write_chunk(&mut form_body, "Stks", &stks_body[..]);
-        QuetzalSave::write_chunk(&mut form_body, "CMem", &cmem_body[..]);
-        QuetzalSave::write_chunk(&mut save_data, "FORM", &form_body[..]);
+    // A save only works with the exact game it came from
+    pub fn check_story(&self, release: u16, serial: &[u8], chksum: u16) -> Result<(), String> {
+        if self.release != release || self.serial[..] != serial[..] {
+            return Err(format!(
+                "save is from a different game (release {} / {}, this is release {} / {})",
+                self.release,
+                String::from_utf8_lossy(&self.serial),
+                release,
+                String::from_utf8_lossy(serial)
+            ));
+        }
 
-        save_data
+        if self.chksum != chksum {
+            return Err(format!(
+                "save is from a different version of this game (checksum {:#06x}, expected {:#06x})",
+                self.chksum, chksum
+            ));
+        }
+
+        Ok(())
     }
 
-    fn read_chunk(data: &[u8]) -> (String, usize, &[u8]) {
-        let header = String::from_utf8_lossy(&data[0..4]).into_owned();
+    // Writes the save in the same layout as the reference interpreters. The
+    // memory gets compressed against the original dynamic memory (CMem)
+    // unless it's asked to be stored as is (UMem).
+    pub fn to_bytes(&self, original: &[u8], version: u8, compress: bool) -> Vec<u8> {
+        let mut save_data = Vec::new();
+        let mut form_body = Vec::from(&b"IFZS"[..]); // Form starts w/ "IFZS"
 
-        let mut body_length = 0;
-        body_length += (data[4] as usize) << 24;
-        body_length += (data[5] as usize) << 16;
-        body_length += (data[6] as usize) << 8;
-        body_length += data[7] as usize;
+        let ifhd_body =
+            QuetzalSave::make_ifhd_body(self.release, &self.serial, self.chksum, self.pc);
+        let stks_body = QuetzalSave::make_stks_body(&self.frames, version);
 
-        let body = &data[8..(8 + body_length)];
+        iff::write_chunk(&mut form_body, "IFhd", &ifhd_body[..]);
 
-        // chunks get padded with an empty 0 byte if they have an odd length
-        let mut chunk_length = 8 + body_length;
-        if chunk_length % 2 != 0 {
-            chunk_length += 1;
+        if compress {
+            let cmem_body = QuetzalSave::make_cmem_body(&self.memory, original);
+            iff::write_chunk(&mut form_body, "CMem", &cmem_body[..]);
+        } else {
+            iff::write_chunk(&mut form_body, "UMem", &self.memory[..]);
         }
 
-        (header, chunk_length, body)
-    }
+        iff::write_chunk(&mut form_body, "Stks", &stks_body[..]);
 
-    fn write_chunk(bytes: &mut Vec<u8>, header: &str, body: &[u8]) {
-        // 4 bytes for the header string
-        bytes.extend(&header.as_bytes()[0..4]);
+        for (header, body) in &self.chunks {
+            iff::write_chunk(&mut form_body, header, &body[..]);
+        }
 
-        // 4 bytes for the length (BE)
-        let length = body.len();
-        bytes.push(((length & 0xFF00_0000) >> 24) as u8);
-        bytes.push(((length & 0x00FF_0000) >> 16) as u8);
-        bytes.push(((length & 0x0000_FF00) >> 8) as u8);
-        bytes.push((length & 0x0000_00FF) as u8);
+        iff::write_chunk(&mut save_data, "FORM", &form_body[..]);
 
-        // + body
-        bytes.extend(body);
+        save_data
+    }
 
-        // If the body length is odd, add a padding byte. This extra byte is
-        // *not* included in the length marker above.
-        if body.len() % 2 != 0 {
-            bytes.push(0);
+    fn read_ifhd_body(&mut self, bytes: &[u8]) -> Result<(), String> {
+        if bytes.len() < 13 {
+            return Err(format!("header (IFhd) is {} bytes, expected 13", bytes.len()));
         }
-    }
 
-    fn is_complete(&self) -> bool {
-        self.pc != 0 && self.chksum != 0 && !self.frames.is_empty() && !self.memory.is_empty()
-    }
+        // 1 word for release
+        self.release = u16::from(bytes[0]) << 8 | u16::from(bytes[1]);
 
-    fn read_ifhd_body(&mut self, bytes: &[u8]) {
-        // 1 word for release (skip, we don't use)
-        // 6 bytes for serial number (also skip)
+        // 6 bytes for serial number
+        self.serial.copy_from_slice(&bytes[2..8]);
 
         // 1 word for checksum
-        self.chksum += u16::from(bytes[8]) << 8;
-        self.chksum += u16::from(bytes[9]);
+        self.chksum = u16::from(bytes[8]) << 8 | u16::from(bytes[9]);
 
         // 3 bytes for PC
-        self.pc += usize::from(bytes[10]) << 16;
-        self.pc += usize::from(bytes[11]) << 8;
-        self.pc += usize::from(bytes[12]);
+        self.pc = usize::from(bytes[10]) << 16 | usize::from(bytes[11]) << 8 | usize::from(bytes[12]);
+
+        Ok(())
     }
 
     fn make_ifhd_body(release: u16, serial: &[u8], chksum: u16, pc: usize) -> [u8; 13] {
@@ -169,7 +196,7 @@ impl QuetzalSave {
         bytes
     }
 
-    fn read_cmem_body(&mut self, compressed: &[u8], original: &[u8]) {
+    fn read_cmem_body(&mut self, compressed: &[u8], original: &[u8]) -> Result<(), String> {
         let mut uncompressed = Vec::new();
         let mut index = 0;
 
@@ -183,25 +210,48 @@ impl QuetzalSave {
             // zero bytes are followed by a length byte, indicating how many
             // 0s go between the previous non-zero byte (above) and the next
             } else {
+                let length = match compressed.get(index + 1) {
+                    Some(&length) => length as usize,
+                    None => return Err("memory (CMem) ends in the middle of a run".to_string()),
+                };
+
                 // +1 for the 0 before the length byte:
-                let length = compressed[index + 1] as usize;
                 uncompressed.extend(vec![0; length + 1]);
                 index += 2;
             }
-        }
-
-        let difference = original.len() - uncompressed.len();
 
-        if difference > 0 {
-            uncompressed.extend(vec![0; difference]);
+            if uncompressed.len() > original.len() {
+                return Err(format!(
+                    "memory (CMem) is longer than the game's dynamic memory ({} bytes)",
+                    original.len()
+                ));
+            }
         }
 
+        // anything past the end of the compressed data is unchanged
+        uncompressed.resize(original.len(), 0);
+
         // XOR uncompressed with original to restore
         self.memory = uncompressed
             .iter()
             .zip(original.iter())
             .map(|(a, b)| a ^ b)
             .collect();
+
+        Ok(())
+    }
+
+    fn read_umem_body(&mut self, bytes: &[u8], original: &[u8]) -> Result<(), String> {
+        if bytes.len() != original.len() {
+            return Err(format!(
+                "memory (UMem) is {} bytes, but the game's dynamic memory is {} bytes",
+                bytes.len(),
+                original.len()
+            ));
+        }
+
+        self.memory = bytes.to_vec();
+        Ok(())
     }
 
     fn make_cmem_body(current: &[u8], original: &[u8]) -> Vec<u8> {
@@ -236,11 +286,18 @@ impl QuetzalSave {
             }).0 // <- compressed is the first field in the tuple
     }
 
-    fn read_stks_body(&mut self, bytes: &[u8]) {
+    // In v1-5 the first frame is a dummy one, which only holds the main
+    // routine's stack (v6 starts with a real frame for the main routine)
+    fn read_stks_body(&mut self, bytes: &[u8], version: u8) -> Result<(), String> {
         let mut frames = Vec::new();
         let mut offset = 0;
 
-        while offset < bytes.len() - 1 {
+        while offset < bytes.len() {
+            // each frame has at least 8 bytes before its locals & stack
+            if offset + 8 > bytes.len() {
+                return Err(format!("stack frame {} (Stks) is truncated", frames.len()));
+            }
+
             // variable lengths found here:
             let num_locals = bytes[offset + 3] & 0b0000_1111;
             let mut stack_length = 0;
@@ -251,21 +308,43 @@ impl QuetzalSave {
             // each value is a 2 byte word
             let end = offset + 8 + num_locals as usize * 2 + stack_length as usize * 2;
 
-            let slice = &bytes[offset..end];
-            let frame = Frame::from_bytes(slice);
+            let slice = bytes.get(offset..end).ok_or_else(|| {
+                format!(
+                    "stack frame {} (Stks) is truncated ({} locals, {} stack values)",
+                    frames.len(),
+                    num_locals,
+                    stack_length
+                )
+            })?;
 
-            frames.push(frame);
+            frames.push(Frame::from_bytes(slice));
             offset += slice.len();
         }
 
+        if frames.is_empty() {
+            return Err("stack (Stks) doesn't have any frames".to_string());
+        }
+
+        if version != 6 {
+            frames[0].store = None;
+        }
+
         self.frames = frames;
+        Ok(())
     }
 
-    fn make_stks_body(frames: &[Frame]) -> Vec<u8> {
+    fn make_stks_body(frames: &[Frame], version: u8) -> Vec<u8> {
         let mut bytes = Vec::new();
 
-        for frame in frames.iter() {
-            bytes.extend(frame.to_vec());
+        for (i, frame) in frames.iter().enumerate() {
+            let mut frame_bytes = frame.to_vec();
+
+            // the dummy frame's pc, flags, store & args are all 0
+            if i == 0 && version != 6 {
+                frame_bytes[0..6].copy_from_slice(&[0; 6]);
+            }
+
+            bytes.extend(frame_bytes);
         }
 
         bytes
@@ -276,8 +355,10 @@ impl fmt::Display for QuetzalSave {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln!(
             f,
-            "PC: {:#04x} Chksum: {:#04x} Mem Length: {}",
+            "PC: {:#04x} Release: {} Serial: {} Chksum: {:#04x} Mem Length: {}",
             self.pc,
+            self.release,
+            String::from_utf8_lossy(&self.serial),
             self.chksum,
             self.memory.len()
         )?;
@@ -289,3 +370,211 @@ impl fmt::Display for QuetzalSave {
         write!(f, "")
     }
 }
+
+// Round trips the saves in tests/quetzal, which are all for minizork.z3. The
+// ones at the top level were written by encrusted (extra.sav has chunks added
+// by hand), so they only show that our reader & writer agree with each other.
+// Saves written by other interpreters go in tests/quetzal/<interpreter>, see
+// tests/quetzal/README.txt.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn tests_dir() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+    }
+
+    // (release, serial, checksum) and the dynamic memory of minizork
+    fn story() -> (u16, Vec<u8>, u16, Vec<u8>) {
+        let data = fs::read(tests_dir().join("minizork.z3")).unwrap();
+        let word = |addr: usize| u16::from(data[addr]) << 8 | u16::from(data[addr + 1]);
+        let static_start = word(0x0E) as usize;
+
+        (word(0x02), data[0x12..0x18].to_vec(), word(0x1C), data[..static_start].to_vec())
+    }
+
+    // other interpreters that should be able to share saves with us
+    const INTERPRETERS: [&str; 2] = ["frotz", "gargoyle"];
+
+    fn corpus() -> Vec<(String, Vec<u8>)> {
+        let saves = saves_in(&tests_dir().join("quetzal"));
+        assert!(!saves.is_empty(), "no saves in tests/quetzal");
+        saves
+    }
+
+    fn saves_in(dir: &PathBuf) -> Vec<(String, Vec<u8>)> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+
+        let mut saves: Vec<_> = entries
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension() == Some("sav".as_ref()))
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, fs::read(&path).unwrap())
+            })
+            .collect();
+
+        saves.sort();
+        saves
+    }
+
+    // the save without its IntD chunks, which are specific to each interpreter
+    fn without_intd(data: &[u8]) -> Vec<u8> {
+        let mut body = b"IFZS".to_vec();
+
+        for (id, _, chunk) in iff::read_form(data, "IFZS").unwrap() {
+            if id != "IntD" {
+                iff::write_chunk(&mut body, &id, chunk);
+            }
+        }
+
+        let mut bytes = Vec::new();
+        iff::write_chunk(&mut bytes, "FORM", &body);
+        bytes
+    }
+
+    fn stks(save: &QuetzalSave) -> Vec<u8> {
+        QuetzalSave::make_stks_body(&save.frames, 3)
+    }
+
+    #[test]
+    fn reads_corpus() {
+        let (release, serial, chksum, original) = story();
+
+        for (name, data) in corpus() {
+            let save = QuetzalSave::from_bytes(&data, &original, 3)
+                .unwrap_or_else(|err| panic!("{}: {}", name, err));
+
+            assert_eq!(save.check_story(release, &serial, chksum), Ok(()), "{}", name);
+            assert_eq!(save.memory.len(), original.len(), "{}", name);
+            assert!(save.frames.len() > 1, "{}", name);
+            assert_eq!(save.frames[0].store, None, "{}: dummy frame", name);
+        }
+    }
+
+    #[test]
+    fn round_trips_corpus() {
+        let (_, _, _, original) = story();
+
+        for (name, data) in corpus() {
+            let save = QuetzalSave::from_bytes(&data, &original, 3).unwrap();
+
+            for &compress in &[true, false] {
+                let bytes = save.to_bytes(&original, 3, compress);
+                let again = QuetzalSave::from_bytes(&bytes, &original, 3).unwrap();
+
+                assert_eq!(again.pc, save.pc, "{}", name);
+                assert_eq!(again.release, save.release, "{}", name);
+                assert_eq!(again.serial, save.serial, "{}", name);
+                assert_eq!(again.chksum, save.chksum, "{}", name);
+                assert_eq!(again.memory, save.memory, "{}", name);
+                assert_eq!(again.chunks, save.chunks, "{}", name);
+                assert_eq!(stks(&again), stks(&save), "{}", name);
+
+                // and writing it out again doesn't change anything
+                assert_eq!(again.to_bytes(&original, 3, compress), bytes, "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs saves written by frotz & gargoyle, see tests/quetzal/README.txt"]
+    fn matches_other_interpreters() {
+        let (release, serial, chksum, original) = story();
+
+        for interpreter in &INTERPRETERS {
+            let saves = saves_in(&tests_dir().join("quetzal").join(interpreter));
+            assert!(!saves.is_empty(), "no saves in tests/quetzal/{}", interpreter);
+
+            for (name, data) in saves {
+                let name = format!("{}/{}", interpreter, name);
+                let save = QuetzalSave::from_bytes(&data, &original, 3)
+                    .unwrap_or_else(|err| panic!("{}: {}", name, err));
+
+                assert_eq!(save.check_story(release, &serial, chksum), Ok(()), "{}", name);
+                assert_eq!(save.frames[0].store, None, "{}: dummy frame", name);
+
+                // writing the same state out again gives the same file
+                let compress = iff::read_form(&data, "IFZS")
+                    .unwrap()
+                    .iter()
+                    .any(|chunk| chunk.0 == "CMem");
+                let ours = save.to_bytes(&original, 3, compress);
+
+                assert_eq!(without_intd(&ours), without_intd(&data), "{}", name);
+            }
+        }
+    }
+
+    // our own saves come back out exactly as they went in
+    #[test]
+    fn writes_reference_layout() {
+        let (_, _, _, original) = story();
+
+        for &(name, compress) in &[("minizork.cmem.sav", true), ("minizork.umem.sav", false)] {
+            let data = fs::read(tests_dir().join("quetzal").join(name)).unwrap();
+            let save = QuetzalSave::from_bytes(&data, &original, 3).unwrap();
+
+            assert_eq!(save.to_bytes(&original, 3, compress), data, "{}", name);
+        }
+    }
+
+    #[test]
+    fn keeps_extra_chunks() {
+        let (_, _, _, original) = story();
+        let data = fs::read(tests_dir().join("quetzal").join("minizork.extra.sav")).unwrap();
+        let save = QuetzalSave::from_bytes(&data, &original, 3).unwrap();
+
+        let ids: Vec<_> = save.chunks.iter().map(|chunk| chunk.0.as_str()).collect();
+        assert_eq!(ids, vec!["IntD", "ANNO", "AUTH", "(c) "]);
+    }
+
+    #[test]
+    fn writes_empty_dummy_frame() {
+        let (_, _, _, original) = story();
+        let data = fs::read(tests_dir().join("quetzal").join("minizork.old.sav")).unwrap();
+        let save = QuetzalSave::from_bytes(&data, &original, 3).unwrap();
+
+        // older saves marked the dummy frame as discarding its result
+        assert_eq!(&stks(&save)[0..6], &[0; 6]);
+    }
+
+    #[test]
+    fn rejects_bad_saves() {
+        let (_, _, _, original) = story();
+        let data = fs::read(tests_dir().join("quetzal").join("minizork.cmem.sav")).unwrap();
+        let save = QuetzalSave::from_bytes(&data, &original, 3).unwrap();
+
+        let truncated = &data[..data.len() - 10];
+        assert!(QuetzalSave::from_bytes(truncated, &original, 3).is_err());
+
+        let not_a_save = b"FORM\x00\x00\x00\x04IFRS";
+        assert!(QuetzalSave::from_bytes(not_a_save, &original, 3).is_err());
+
+        // a run of zeros that's missing its length byte
+        let mut body = Vec::new();
+        for (id, _, chunk) in iff::read_form(&data, "IFZS").unwrap() {
+            let mut chunk = chunk.to_vec();
+            if id == "CMem" {
+                chunk.push(0);
+            }
+            iff::write_chunk(&mut body, &id, &chunk);
+        }
+        let mut broken = Vec::new();
+        iff::write_chunk(&mut broken, "FORM", &[&b"IFZS"[..], &body].concat());
+        assert!(QuetzalSave::from_bytes(&broken, &original, 3).is_err());
+
+        // memory that's too big for the game
+        let umem = QuetzalSave { memory: vec![0; original.len() + 1], ..save };
+        let too_big = umem.to_bytes(&original, 3, false);
+        assert!(QuetzalSave::from_bytes(&too_big, &original, 3).is_err());
+
+        let other_game = QuetzalSave::from_bytes(&data, &original, 3).unwrap();
+        assert!(other_game.check_story(1, b"000000", 0).is_err());
+    }
+}
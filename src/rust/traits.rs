@@ -1,3 +1,28 @@
+// Text style & colour state. Colours are 15-bit true colour values
+// (0bbbbbgggggrrrrr), `None` means the UI's default colour
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub reverse: bool,
+    pub bold: bool,
+    pub italic: bool,
+    pub fixed: bool,
+    pub foreground: Option<u16>,
+    pub background: Option<u16>,
+}
+
+impl Style {
+    // expands a 15-bit true colour into 8-bit rgb components
+    pub fn rgb(colour: u16) -> (u8, u8, u8) {
+        let expand = |c: u16| ((c << 3) | (c >> 2)) as u8;
+
+        (
+            expand(colour & 0x1F),
+            expand((colour >> 5) & 0x1F),
+            expand((colour >> 10) & 0x1F),
+        )
+    }
+}
+
 pub trait UI {
     fn new() -> Box<Self>
     where
@@ -8,9 +33,51 @@ pub trait UI {
     fn print_object(&mut self, object: &str);
     fn set_status_bar(&self, left: &str, right: &str);
 
+    // screen model (upper window = 1, lower window = 0)
+    fn split_window(&mut self, lines: u16);
+    fn set_window(&mut self, window: u16);
+    fn erase_window(&mut self, window: i16);
+    fn erase_line(&mut self);
+    fn set_cursor(&mut self, line: u16, column: u16);
+    fn get_cursor(&mut self) -> (u16, u16);
+
+    // text styles & colours
+    fn set_style(&mut self, style: Style);
+    fn supports_styles(&self) -> bool;
+    fn supports_colours(&self) -> bool;
+
+    // screen size as (lines, columns), 255 lines means no paging
+    fn get_screen_size(&self) -> (u16, u16);
+    // default colours as (foreground, background) colour numbers
+    fn get_default_colours(&self) -> (u8, u8);
+
+    // sound effects, numbered by their blorb resource (1 & 2 are bleeps).
+    // Volume is 1-8 and repeats of 255 mean loop forever.
+    fn supports_sound(&self) -> bool;
+    fn bleep(&mut self, number: u16);
+    fn load_sound(&mut self, number: u16, kind: &str, data: &[u8]);
+    fn start_sound(&mut self, number: u16, volume: u8, repeats: u8);
+    fn stop_sound(&mut self, number: u16);
+    fn unload_sound(&mut self, number: u16);
+
     // only used by terminal ui
     fn reset(&self);
-    fn get_user_input(&self) -> String;
+    fn get_user_input(&mut self) -> String;
+    // Timed input: `timeout` is in tenths of a second (0 = wait forever).
+    // Line input is edited in place and finishes with enter or one of the
+    // `terminators` (function keys), returning that key, or None if time
+    // ran out first. `redraw` reprints the partial input first.
+    fn get_timed_input(
+        &mut self,
+        input: &mut String,
+        timeout: u16,
+        redraw: bool,
+        terminators: &[u16],
+    ) -> Option<u16>;
+    fn get_user_key(&mut self, timeout: u16) -> Option<u16>;
+    // the game's unicode characters for zscii 155 and up, so keys typed
+    // with them can be read as zscii
+    fn set_unicode_table(&mut self, table: &[char]);
 
     // only used by web ui
     fn flush(&mut self);
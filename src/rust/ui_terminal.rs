@@ -1,14 +1,19 @@
 #![allow(dead_code)]
 
 use std::boxed::Box;
+use std::cmp;
+use std::collections::VecDeque;
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::process::{self, Command, Stdio};
+use std::time::{Duration, Instant};
 
 use regex::Regex;
 use atty::Stream;
 use term_size;
 
-use traits::UI;
+use traits::{Style, UI};
+use zscii::{char_to_zscii, zscii_to_char};
 
 lazy_static! {
     static ref ANSI_RE: Regex = Regex::new(
@@ -16,14 +21,137 @@ lazy_static! {
     ).unwrap();
 }
 
+// runs `stty` against the terminal on stdin, returning its output
+fn stty(args: &[&str]) -> String {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_string())
+        .unwrap_or_default()
+}
+
+// Puts the tty into raw mode (reads time out after 0.1s) for as long as it's
+// around. The old settings come back when it's dropped, which also happens
+// when unwinding from a panic. Ctrl-C doesn't send SIGINT in raw mode (it's
+// read as key 3 instead), so quitting that way doesn't leave the tty broken.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn new() -> RawMode {
+        let saved = stty(&["-g"]);
+        stty(&["-icanon", "-echo", "-isig", "min", "0", "time", "1"]);
+        RawMode { saved }
+    }
+
+    fn restore(&self) {
+        stty(&[&self.saved]);
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+// timeouts are given in tenths of a second, 0 means no timeout
+fn deadline(timeout: u16) -> Option<Instant> {
+    if timeout == 0 {
+        None
+    } else {
+        Some(Instant::now() + Duration::from_millis(u64::from(timeout) * 100))
+    }
+}
+
+// Turns the bytes read from the tty into zscii key codes. Cursor keys are
+// 129-132 and function keys 133-144, anything unrecognized is ignored.
+// Characters outside of ascii are looked up in the game's `unicode_table`.
+fn parse_keys(bytes: &[u8], unicode_table: &[char]) -> Vec<u16> {
+    let escape = match bytes {
+        [27] => Some(27),
+        [27, b'[', dir] | [27, b'O', dir] if *dir >= b'A' && *dir <= b'D' => {
+            // up, down, right, left -> 129, 130, 132, 131
+            Some(match *dir {
+                b'A' => 129,
+                b'B' => 130,
+                b'C' => 132,
+                _ => 131,
+            })
+        }
+        [27, b'O', f] if *f >= b'P' && *f <= b'S' => Some(133 + u16::from(*f - b'P')),
+        [27, b'[', rest @ ..] if rest.last() == Some(&b'~') => {
+            let num = String::from_utf8_lossy(&rest[..rest.len() - 1]);
+
+            match num.parse::<u16>() {
+                Ok(n @ 11..=15) => Some(133 + n - 11),
+                Ok(n @ 17..=21) => Some(138 + n - 17),
+                Ok(n @ 23..=24) => Some(143 + n - 23),
+                _ => None,
+            }
+        }
+        [27, ..] => None,
+        // anything else is (possibly several) typed characters
+        _ => {
+            return String::from_utf8_lossy(bytes)
+                .chars()
+                .filter_map(|chr| match chr as u32 {
+                    8 | 127 => Some(8),
+                    10 | 13 => Some(13),
+                    32..=126 => Some(chr as u16),
+                    0..=31 => None,
+                    _ => Some(char_to_zscii(chr, unicode_table).unwrap_or(u16::from(b'?'))),
+                })
+                .collect();
+        }
+    };
+
+    escape.into_iter().collect()
+}
+
 #[derive(Debug)]
 pub struct TerminalUI {
     isatty: bool,
     width: usize,
+    height: usize,
     x_position: usize,
+    y_position: usize,
+    window: u16,
+    upper_height: usize,
+    upper_cursor: (usize, usize),
+    style: Style,
+    pending_keys: VecDeque<u16>,
+    unicode_table: Vec<char>,
 }
 
 impl TerminalUI {
+    // Waits for a keypress (the tty needs to be in raw mode) until `deadline`.
+    // Ctrl-C quits, the same as it would outside of raw mode.
+    fn poll_key(&mut self, raw: &RawMode, deadline: Option<Instant>) -> Option<u16> {
+        let mut buf = [0; 16];
+
+        while self.pending_keys.is_empty() {
+            if deadline.is_some_and(|time| Instant::now() >= time) {
+                return None;
+            }
+
+            // escape sequences arrive all at once, so one read gets a whole key
+            let count = io::stdin().read(&mut buf).expect("Error reading input");
+
+            if buf[..count].contains(&3) {
+                raw.restore();
+                self.reset();
+                process::exit(130);
+            }
+
+            self.pending_keys.extend(parse_keys(&buf[..count], &self.unicode_table));
+        }
+
+        self.pending_keys.pop_front()
+    }
+
     fn print_raw(&self, raw: &str) {
         print!("{}", raw);
         io::stdout().flush().unwrap();
@@ -44,23 +172,124 @@ impl TerminalUI {
     fn is_term(&self) -> bool {
         self.isatty
     }
+
+    fn move_cursor(&self, line: usize, column: usize) {
+        self.print_raw(&format!("\x1B[{};{}H", line, column));
+    }
+
+    // puts the terminal's cursor back where the current window has it. The
+    // lower window's cursor is tracked by hand rather than saved with ESC 7,
+    // since there's only one slot for that and the upper window needs it too.
+    fn restore_cursor(&self) {
+        if self.window == 1 {
+            self.move_cursor(self.upper_cursor.0, self.upper_cursor.1);
+        } else {
+            self.move_cursor(self.y_position, self.x_position + 1);
+        }
+    }
+
+    // the lower window scrolls inside its own region below the upper window
+    // (setting a scroll region homes the cursor, so it gets put back after)
+    fn set_scroll_region(&self) {
+        if self.upper_height == 0 {
+            self.print_raw("\x1B[r");
+        } else {
+            let top = self.upper_height + 1;
+            self.print_raw(&format!("\x1B[{};{}r", top, self.height));
+        }
+
+        self.restore_cursor();
+    }
+
+    fn clear_lines(&self, first: usize, last: usize) {
+        for line in first..=last {
+            self.move_cursor(line, 1);
+            self.print_raw("\x1B[2K");
+        }
+    }
+
+    // builds an SGR sequence for the current style (always resets first)
+    fn apply_style(&self) {
+        if !self.is_term() {
+            return;
+        }
+
+        let style = &self.style;
+        let mut sgr = String::from("\x1B[0");
+
+        if style.bold {
+            sgr.push_str(";1");
+        }
+        if style.italic {
+            sgr.push_str(";3");
+        }
+        if style.reverse {
+            sgr.push_str(";7");
+        }
+        if let Some(colour) = style.foreground {
+            let (r, g, b) = Style::rgb(colour);
+            sgr.push_str(&format!(";38;2;{};{};{}", r, g, b));
+        }
+        if let Some(colour) = style.background {
+            let (r, g, b) = Style::rgb(colour);
+            sgr.push_str(&format!(";48;2;{};{};{}", r, g, b));
+        }
+
+        sgr.push('m');
+        self.print_raw(&sgr);
+    }
+
+    fn line_break(&mut self) {
+        println!();
+        self.x_position = 0;
+        self.y_position = cmp::min(self.y_position + 1, self.height);
+    }
+
+    // The upper window is a fixed grid: text doesn't wrap or scroll, anything
+    // past the right edge gets clipped. It's dropped entirely when stdout
+    // isn't a terminal, since there's no way to position it.
+    fn print_upper(&mut self, text: &str) {
+        if !self.is_term() {
+            return;
+        }
+
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.upper_cursor = (self.upper_cursor.0 + 1, 1);
+                self.move_cursor(self.upper_cursor.0, self.upper_cursor.1);
+            }
+
+            let room = (self.width + 1).saturating_sub(self.upper_cursor.1);
+            let clipped = line.chars().take(room).collect::<String>();
+
+            self.upper_cursor.1 += clipped.chars().count();
+            print!("{}", clipped);
+        }
+
+        io::stdout().flush().unwrap();
+    }
 }
 
 impl UI for TerminalUI {
     fn new() -> Box<TerminalUI> {
-        if let Some((w, _)) = term_size::dimensions() {
-            Box::new(TerminalUI {
-                isatty: atty::is(Stream::Stdout),
-                width: w,
-                x_position: 0,
-            })
-        } else {
-            Box::new(TerminalUI {
-                isatty: false,
-                width: 0,
-                x_position: 0,
-            })
-        }
+        let (isatty, width, height) = match term_size::dimensions() {
+            Some((w, h)) => (atty::is(Stream::Stdout), w, h),
+            None => (false, 0, 0),
+        };
+
+        Box::new(TerminalUI {
+            isatty,
+            width,
+            height,
+            x_position: 0,
+            y_position: 1,
+            window: 0,
+            upper_height: 0,
+            upper_cursor: (1, 1),
+            style: Style::default(),
+            pending_keys: VecDeque::new(),
+            unicode_table: Vec::new(),
+        })
     }
 
     fn clear(&self) {
@@ -72,6 +301,11 @@ impl UI for TerminalUI {
     }
 
     fn print(&mut self, text: &str) {
+        if self.window == 1 {
+            self.print_upper(text);
+            return;
+        }
+
         if !self.is_term() {
             self.print_raw(text);
             return;
@@ -92,11 +326,11 @@ impl UI for TerminalUI {
                 // check that each word can fit on the line before printing it.
                 // if its too big, bump to the next line and reset x-position
                 words.iter().enumerate().for_each(|(i, word)| {
-                    self.x_position += word.len();
+                    self.x_position += word.chars().count();
 
                     if self.x_position > self.width {
-                        self.x_position = word.len();
-                        println!();
+                        self.line_break();
+                        self.x_position = word.chars().count();
                     }
 
                     print!("{}", word);
@@ -111,8 +345,7 @@ impl UI for TerminalUI {
 
             // add newlines back that were removed from split
             if i < num_lines - 1 {
-                println!();
-                self.x_position = 0;
+                self.line_break();
             }
         });
 
@@ -128,9 +361,7 @@ impl UI for TerminalUI {
             self.print_raw("\x1B[37;1m");
         }
         self.print(object);
-        if self.is_term() {
-            self.print_raw("\x1B[0m");
-        }
+        self.apply_style();
     }
 
     fn set_status_bar(&self, left: &str, right: &str) {
@@ -140,12 +371,167 @@ impl UI for TerminalUI {
         }
     }
 
-    fn get_user_input(&self) -> String {
+    fn split_window(&mut self, lines: u16) {
+        self.upper_height = cmp::min(lines as usize, self.height);
+
+        if !self.is_term() {
+            return;
+        }
+
+        self.set_scroll_region();
+
+        // keep the lower window's cursor out of the newly split upper window
+        if self.window == 0 && self.y_position <= self.upper_height {
+            self.y_position = self.upper_height + 1;
+            self.x_position = 0;
+            self.move_cursor(self.y_position, 1);
+        }
+    }
+
+    fn set_window(&mut self, window: u16) {
+        if window == self.window {
+            return;
+        }
+
+        self.window = window;
+
+        if !self.is_term() {
+            return;
+        }
+
+        // selecting the upper window homes its cursor, the lower window
+        // picks up wherever it left off
+        if window == 1 {
+            self.upper_cursor = (1, 1);
+        }
+
+        self.restore_cursor();
+    }
+
+    fn erase_window(&mut self, window: i16) {
+        // -1 unsplits the screen and clears everything, -2 just clears it
+        if window == -1 {
+            self.upper_height = 0;
+            self.window = 0;
+        }
+
+        match window {
+            -1 | -2 => {
+                self.upper_cursor = (1, 1);
+                self.x_position = 0;
+                self.y_position = self.upper_height + 1;
+
+                if self.is_term() {
+                    self.set_scroll_region();
+                    self.print_raw("\x1B[2J");
+                    self.move_cursor(self.y_position, 1);
+                }
+            }
+            0 => {
+                self.x_position = 0;
+                self.y_position = self.upper_height + 1;
+
+                if self.is_term() {
+                    self.clear_lines(self.upper_height + 1, self.height);
+                    self.move_cursor(self.y_position, 1);
+                }
+            }
+            1 => {
+                self.upper_cursor = (1, 1);
+
+                // (the cursor goes to the top left if the upper window is
+                // selected, otherwise back to the lower window)
+                if self.is_term() {
+                    if self.upper_height > 0 {
+                        self.clear_lines(1, self.upper_height);
+                    }
+                    self.restore_cursor();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn erase_line(&mut self) {
+        // clears from the cursor to the end of the line
+        if self.is_term() {
+            self.print_raw("\x1B[K");
+        }
+    }
+
+    fn set_cursor(&mut self, line: u16, column: u16) {
+        if self.window != 1 {
+            return;
+        }
+
+        self.upper_cursor = (cmp::max(line as usize, 1), cmp::max(column as usize, 1));
+
+        if self.is_term() {
+            self.move_cursor(self.upper_cursor.0, self.upper_cursor.1);
+        }
+    }
+
+    fn get_cursor(&mut self) -> (u16, u16) {
+        if self.window == 1 {
+            (self.upper_cursor.0 as u16, self.upper_cursor.1 as u16)
+        } else {
+            (self.y_position as u16, self.x_position as u16 + 1)
+        }
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+        self.apply_style();
+    }
+
+    fn supports_styles(&self) -> bool {
+        self.is_term()
+    }
+
+    fn supports_colours(&self) -> bool {
+        self.is_term()
+    }
+
+    // without a terminal, output is just a stream that never needs paging
+    fn get_screen_size(&self) -> (u16, u16) {
+        if self.is_term() {
+            (self.height as u16, self.width as u16)
+        } else {
+            (255, 80)
+        }
+    }
+
+    // only the terminal bell is available, for bleeps
+    fn supports_sound(&self) -> bool {
+        false
+    }
+
+    fn bleep(&mut self, _number: u16) {
+        if self.is_term() {
+            self.print_raw("\x07");
+        }
+    }
+
+    fn load_sound(&mut self, _number: u16, _kind: &str, _data: &[u8]) {}
+    fn start_sound(&mut self, _number: u16, _volume: u8, _repeats: u8) {}
+    fn stop_sound(&mut self, _number: u16) {}
+    fn unload_sound(&mut self, _number: u16) {}
+
+    // most terminals default to white on black
+    fn get_default_colours(&self) -> (u8, u8) {
+        (9, 2)
+    }
+
+    fn get_user_input(&mut self) -> String {
         let mut input = String::new();
         io::stdin()
             .read_line(&mut input)
             .expect("Error reading input");
 
+        // the terminal echoes the enter key, moving down a line
+        self.x_position = 0;
+        self.y_position = cmp::min(self.y_position + 1, self.height);
+
         // trim, strip and control sequences that might have gotten in,
         // and then trim once more to get rid of any excess whitespace
         ANSI_RE
@@ -155,7 +541,96 @@ impl UI for TerminalUI {
             .to_string()
     }
 
+    // Line input with a time limit (or extra terminating keys), done with our
+    // own line editing since the tty has to be in raw mode to poll it. When
+    // stdin isn't a terminal it can't be polled, so this just reads a line.
+    fn get_timed_input(
+        &mut self,
+        input: &mut String,
+        timeout: u16,
+        redraw: bool,
+        terminators: &[u16],
+    ) -> Option<u16> {
+        if !atty::is(Stream::Stdin) {
+            input.push_str(&self.get_user_input());
+            return Some(13);
+        }
+
+        if redraw {
+            print!("{}", input);
+            self.x_position += input.len();
+        }
+
+        io::stdout().flush().unwrap();
+
+        let raw = RawMode::new();
+        let deadline = deadline(timeout);
+
+        let terminator = loop {
+            match self.poll_key(&raw, deadline) {
+                Some(13) => break Some(13),
+                Some(key) if terminators.contains(&key) => break Some(key),
+                Some(8) => {
+                    if input.pop().is_some() {
+                        print!("\x08 \x08");
+                        self.x_position = self.x_position.saturating_sub(1);
+                    }
+                }
+                Some(key @ 32..=126) => {
+                    input.push(key as u8 as char);
+                    print!("{}", key as u8 as char);
+                    self.x_position += 1;
+                }
+                Some(key @ 155..=251) => {
+                    if let Some(chr) = zscii_to_char(key, &self.unicode_table) {
+                        input.push(chr);
+                        print!("{}", chr);
+                        self.x_position += 1;
+                    }
+                }
+                Some(_) => (),
+                None => break None,
+            }
+
+            io::stdout().flush().unwrap();
+        };
+
+        drop(raw);
+
+        if terminator.is_some() {
+            self.line_break();
+        }
+
+        terminator
+    }
+
+    // Reads a single keystroke by temporarily putting the tty into raw mode.
+    // When stdin isn't a terminal, reads a line and takes its first key.
+    fn get_user_key(&mut self, timeout: u16) -> Option<u16> {
+        if !atty::is(Stream::Stdin) {
+            let input = self.get_user_input();
+            let keys = parse_keys(input.as_bytes(), &self.unicode_table);
+            return Some(keys.first().cloned().unwrap_or(13));
+        }
+
+        io::stdout().flush().unwrap();
+
+        let raw = RawMode::new();
+        self.poll_key(&raw, deadline(timeout))
+    }
+
+    fn set_unicode_table(&mut self, table: &[char]) {
+        self.unicode_table = table.to_vec();
+    }
+
     fn reset(&self) {
+        if self.is_term() {
+            self.print_raw("\x1B[0m");
+        }
+        if self.is_term() && self.upper_height > 0 {
+            self.print_raw("\x1B[r");
+            self.restore_cursor();
+        }
         println!();
     }
 
@@ -2,10 +2,11 @@ use std::boxed::Box;
 use std::ffi::CString;
 use std::fmt::Write;
 
+use base64;
 use serde_json;
 
 use js_message;
-use traits::UI;
+use traits::{Style, UI};
 
 #[derive(Debug)]
 enum Token {
@@ -13,19 +14,158 @@ enum Token {
     Text(String),
     Object(String),
     Debug(String),
+    Style(Style),
 }
 
+impl Token {
+    fn is_style(&self) -> bool {
+        matches!(*self, Token::Style(_))
+    }
+}
+
+// screen size until the front end reports its own; the transcript scrolls
+// so there's never a need to page
+const DEFAULT_SIZE: (u16, u16) = (255, 80);
+
 #[derive(Debug)]
 pub struct WebUI {
     buffer: Vec<Token>,
+    window: u16,
+    grid: Vec<Vec<char>>,
+    grid_cursor: (usize, usize),
+    grid_changed: bool,
+    style: Style,
+    flushed_style: Style,
+    size: (u16, u16),
+}
+
+impl WebUI {
+    // Web UI only
+    #[allow(dead_code)]
+    pub fn set_screen_size(&mut self, lines: u16, columns: u16) {
+        if lines > 0 && columns > 0 {
+            self.size = (lines, columns);
+        }
+    }
+
+    // width of the upper window grid, in characters
+    fn grid_width(&self) -> usize {
+        self.size.1 as usize
+    }
+
+    // The upper window is kept as a grid of characters and is sent to the
+    // front end as a whole (as a list of lines) whenever it changes.
+    fn print_grid(&mut self, text: &str) {
+        let width = self.grid_width();
+
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.grid_cursor = (self.grid_cursor.0 + 1, 0);
+            }
+
+            for chr in line.chars() {
+                let (row, col) = self.grid_cursor;
+
+                if row < self.grid.len() && col < width {
+                    self.grid[row][col] = chr;
+                }
+
+                self.grid_cursor.1 += 1;
+            }
+        }
+
+        self.grid_changed = true;
+    }
+
+    fn flush_grid(&mut self) {
+        if !self.grid_changed {
+            return;
+        }
+
+        let lines = self
+            .grid
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect::<Vec<_>>();
+
+        let msg = serde_json::to_string(&lines).unwrap();
+        self.message("window", &msg);
+        self.grid_changed = false;
+    }
+
+    fn open_span(style: &Style) -> String {
+        if *style == Style::default() {
+            return String::from("<span>");
+        }
+
+        let mut classes = Vec::new();
+        let mut css = String::new();
+
+        if style.bold {
+            classes.push("bold");
+        }
+        if style.italic {
+            classes.push("italic");
+        }
+        if style.fixed {
+            classes.push("fixed");
+        }
+
+        // reverse video swaps the colours, or falls back to a css class
+        // when both of them are just the defaults
+        let (fg, bg) = if style.reverse {
+            (style.background, style.foreground)
+        } else {
+            (style.foreground, style.background)
+        };
+
+        if style.reverse && fg.is_none() && bg.is_none() {
+            classes.push("reverse");
+        }
+
+        if let Some(colour) = fg {
+            let (r, g, b) = Style::rgb(colour);
+            write!(css, "color:#{:02x}{:02x}{:02x};", r, g, b).unwrap();
+        }
+        if let Some(colour) = bg {
+            let (r, g, b) = Style::rgb(colour);
+            write!(css, "background-color:#{:02x}{:02x}{:02x};", r, g, b).unwrap();
+        }
+
+        let mut span = String::from("<span");
+
+        if !classes.is_empty() {
+            write!(span, r#" class="{}""#, classes.join(" ")).unwrap();
+        }
+        if !css.is_empty() {
+            write!(span, r#" style="{}""#, css).unwrap();
+        }
+
+        span.push('>');
+        span
+    }
 }
 
 impl UI for WebUI {
     fn new() -> Box<WebUI> {
-        Box::new(WebUI { buffer: Vec::new() })
+        Box::new(WebUI {
+            buffer: Vec::new(),
+            window: 0,
+            grid: Vec::new(),
+            grid_cursor: (0, 0),
+            grid_changed: false,
+            style: Style::default(),
+            flushed_style: Style::default(),
+            size: DEFAULT_SIZE,
+        })
     }
 
     fn print(&mut self, text: &str) {
+        if self.window == 1 {
+            self.print_grid(text);
+            return;
+        }
+
         if text.is_empty() {
             return;
         }
@@ -66,11 +206,16 @@ impl UI for WebUI {
     }
 
     fn flush(&mut self) {
-        if self.buffer.is_empty() {
+        self.flush_grid();
+
+        if self.buffer.iter().all(Token::is_style) {
+            self.buffer.clear();
+            self.flushed_style = self.style;
             return;
         }
 
         let mut html = String::new();
+        let mut style = self.flushed_style;
 
         for (index, item) in self.buffer.iter().enumerate() {
             let prev = if index == 0 {
@@ -88,7 +233,7 @@ impl UI for WebUI {
                 Token::Text(ref text) => {
                     match prev {
                         Some(&Token::Text(_)) => (),
-                        _ => html.push_str("<span>"),
+                        _ => html.push_str(&WebUI::open_span(&style)),
                     }
 
                     html.push_str(&text);
@@ -99,6 +244,10 @@ impl UI for WebUI {
                     }
                 }
                 Token::Object(ref obj) => {
+                    // style changes around an object don't affect its class
+                    let prev = self.buffer[..index].iter().rev().find(|t| !t.is_style());
+                    let next = self.buffer[index + 1..].iter().find(|t| !t.is_style());
+
                     let class = match (prev, next) {
                         (None, Some(&Token::Newline)) => "room",
                         (Some(&Token::Newline), Some(&Token::Newline)) => "room",
@@ -110,9 +259,14 @@ impl UI for WebUI {
                 Token::Debug(ref text) => {
                     write!(html, r#"<span class="debug">{}</span>"#, text).unwrap();
                 }
+                Token::Style(next_style) => {
+                    style = next_style;
+                }
             }
         }
 
+        self.flushed_style = style;
+
         self.message("print", &html);
         self.buffer.clear();
     }
@@ -133,9 +287,130 @@ impl UI for WebUI {
         }
     }
 
+    fn split_window(&mut self, lines: u16) {
+        let width = self.grid_width();
+        self.grid.resize(lines as usize, vec![' '; width]);
+        self.grid_changed = true;
+    }
+
+    fn set_window(&mut self, window: u16) {
+        self.window = window;
+
+        if window == 1 {
+            self.grid_cursor = (0, 0);
+        }
+    }
+
+    // The lower window is a scrolling transcript on the web, so only the
+    // upper window grid ever actually gets erased
+    fn erase_window(&mut self, window: i16) {
+        match window {
+            -1 => {
+                self.grid.clear();
+                self.window = 0;
+            }
+            -2 | 1 => {
+                let width = self.grid_width();
+                for row in &mut self.grid {
+                    *row = vec![' '; width];
+                }
+            }
+            _ => return,
+        }
+
+        self.grid_cursor = (0, 0);
+        self.grid_changed = true;
+    }
+
+    fn erase_line(&mut self) {
+        if self.window != 1 {
+            return;
+        }
+
+        let (row, col) = self.grid_cursor;
+
+        if let Some(line) = self.grid.get_mut(row) {
+            for chr in line.iter_mut().skip(col) {
+                *chr = ' ';
+            }
+            self.grid_changed = true;
+        }
+    }
+
+    fn set_cursor(&mut self, line: u16, column: u16) {
+        if self.window == 1 {
+            self.grid_cursor = (line.max(1) as usize - 1, column.max(1) as usize - 1);
+        }
+    }
+
+    fn get_cursor(&mut self) -> (u16, u16) {
+        if self.window == 1 {
+            (self.grid_cursor.0 as u16 + 1, self.grid_cursor.1 as u16 + 1)
+        } else {
+            (1, 1)
+        }
+    }
+
+    fn set_style(&mut self, style: Style) {
+        self.style = style;
+        self.buffer.push(Token::Style(style));
+    }
+
+    fn supports_styles(&self) -> bool {
+        true
+    }
+
+    fn supports_colours(&self) -> bool {
+        true
+    }
+
+    fn get_screen_size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    // The front end gets sent the blorb data for each sound (AIFF/OGG) to
+    // play however it can, then tells the worker when one finishes
+    fn supports_sound(&self) -> bool {
+        true
+    }
+
+    fn bleep(&mut self, number: u16) {
+        self.message("bleep", &number.to_string());
+    }
+
+    fn load_sound(&mut self, number: u16, kind: &str, data: &[u8]) {
+        let msg = (number, kind.trim(), base64::encode(data));
+        self.message("sound_load", &serde_json::to_string(&msg).unwrap());
+    }
+
+    fn start_sound(&mut self, number: u16, volume: u8, repeats: u8) {
+        let msg = (number, volume, repeats);
+        self.message("sound_start", &serde_json::to_string(&msg).unwrap());
+    }
+
+    fn stop_sound(&mut self, number: u16) {
+        self.message("sound_stop", &number.to_string());
+    }
+
+    fn unload_sound(&mut self, number: u16) {
+        self.message("sound_unload", &number.to_string());
+    }
+
+    // dark text on a light page
+    fn get_default_colours(&self) -> (u8, u8) {
+        (2, 9)
+    }
+
     fn clear(&self) {}
     fn reset(&self) {}
-    fn get_user_input(&self) -> String {
+    fn get_user_input(&mut self) -> String {
+        unimplemented!();
+    }
+    fn get_timed_input(&mut self, _: &mut String, _: u16, _: bool, _: &[u16]) -> Option<u16> {
+        unimplemented!();
+    }
+    fn get_user_key(&mut self, _: u16) -> Option<u16> {
         unimplemented!();
     }
+    fn set_unicode_table(&mut self, _: &[char]) {}
 }
@@ -1,12 +1,14 @@
 
 use std::boxed::Box;
-use std::collections::{HashMap, HashSet};
+use std::cell::Cell;
+use std::cmp;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::env;
 use std::fmt;
 use std::fmt::Write as FmtWrite;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
 use std::str;
 
@@ -16,16 +18,47 @@ use rand;
 use rand::{Rng, SeedableRng};
 use serde_json;
 
+use blorb::{Blorb, Usage};
 use buffer::Buffer;
+use error::{Cause, ZmachineError};
 use frame::Frame;
 use instruction::Branch;
 use instruction::Instruction;
 use instruction::Opcode;
 use instruction::Operand;
 use instruction::OperandType;
-use options::Options;
+use options::{ErrorLevel, Options};
 use quetzal::QuetzalSave;
-use traits::UI;
+use traits::{Style, UI};
+use zscii::{char_to_zscii, zscii_to_char, DEFAULT_UNICODE};
+
+// header values identifying the interpreter (spec 11.1.3): an IBM PC is the
+// safest choice, since some games change behaviour for the other machines
+const INTERPRETER_NUMBER: u8 = 6;
+const INTERPRETER_VERSION: u8 = b'A';
+
+// how many save_undo snapshots are kept, the oldest are dropped past this
+// (games call save_undo every turn, so it would grow without limit)
+const UNDO_SLOTS: usize = 100;
+
+// version of the standard this interpreter follows (major, minor)
+const STANDARD_REVISION: (u8, u8) = (1, 1);
+
+// true colour equivalents of the standard colours 2-12 (spec 8.3.7)
+const STANDARD_COLOURS: [u16; 11] = [
+    0x0000, 0x001D, 0x0340, 0x03BD, 0x59A0, 0x7C1F, 0x77A0, 0x7FFF, 0x5AD6, 0x4631, 0x2D6B,
+];
+
+// Unicode stand-ins for the character graphics font (font 3), zscii 32-126.
+// Mostly line & block drawing, with runes in place of the lowercase letters.
+const FONT3: [char; 95] = [
+    ' ', '←', '→', '╱', '╲', ' ', '─', '─', '│', '│', '┴', '┬', '├', '┤', '└', '┌', '┐', '┘',
+    '└', '┌', '┐', '┘', '█', '▀', '▄', '▌', '▐', '▄', '▀', '▌', '▐', '▝', '▗', '▖', '▘', '▝',
+    '▗', '▖', '▘', '▔', '▁', '▏', '▕', ' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉', '█', '▉', '▊',
+    '▋', '▌', '▍', '▎', '▏', '↑', '↓', '↕', '┼', '?', '?', 'ᚪ', 'ᛒ', 'ᚳ', 'ᛞ', 'ᛖ', 'ᚠ', 'ᚷ',
+    'ᚻ', 'ᛁ', 'ᛄ', 'ᛱ', 'ᛚ', 'ᛗ', 'ᚾ', 'ᚩ', 'ᛈ', 'ᛩ', 'ᚱ', 'ᛋ', 'ᛏ', 'ᚢ', 'ᚡ', 'ᚹ', 'ᛉ',
+    'ᚣ', 'ᛎ', '⇑', '⇓', '⇕', '?',
+];
 
 #[derive(Debug)]
 enum ZStringState {
@@ -123,6 +156,19 @@ impl ObjectProperty {
     }
 }
 
+// Where the globals and the object table are in dynamic memory
+// Terminal UI only (for the save inspector)
+#[allow(dead_code)]
+pub struct Layout {
+    pub globals: usize,
+    pub prop_defaults: usize,
+    pub objects: usize,
+    pub object_count: usize,
+    pub obj_size: usize,
+    pub attr_width: usize,
+    pub small_objects: bool, // v1-3 use 1 byte object numbers
+}
+
 pub struct Zmachine {
     pub ui: Box<dyn UI>,
     pub options: Options,
@@ -130,14 +176,15 @@ pub struct Zmachine {
     version: u8,
     memory: Buffer,
     original_dynamic: Vec<u8>,
+    checksum: u16,
     save_dir: String,
     save_name: String,
     static_start: usize,
     routine_offset: usize,
     string_offset: usize,
     alphabet: [Vec<String>; 3],
+    unicode_table: Vec<char>,
     abbrev_table: usize,
-    separators: Vec<char>,
     dictionary: HashMap<String, usize>,
     frames: Vec<Frame>,
     initial_pc: usize,
@@ -148,14 +195,34 @@ pub struct Zmachine {
     obj_size: usize,
     attr_width: usize,
     paused_instr: Option<Instruction>,
+    paused_args: Vec<u16>,
+    window: u16,
+    style: Style,
+    font: u16,
+    fixed_pitch: bool,
+    screen_stream: bool,
+    screen_printed: bool,
+    memory_streams: Vec<(usize, usize)>,
+    transcript: Option<File>,
+    command_record: Option<File>,
+    command_script: VecDeque<String>,
     current_state: Option<(String, Vec<u8>)>,
     undos: Vec<(String, Vec<u8>)>,
     redos: Vec<(String, Vec<u8>)>,
+    undo_saves: VecDeque<Vec<u8>>,
+    save_chunks: Vec<(String, Vec<u8>)>,
+    resources: Option<Blorb>,
+    loaded_sounds: HashSet<u16>,
+    sound_routines: HashMap<u16, u16>,
+    finished_sounds: VecDeque<u16>,
     rng: rand::XorShiftRng,
+    fault: Cell<Option<Cause>>,
+    warned: HashSet<(&'static str, usize)>,
 }
 
 impl Zmachine {
     pub fn new(data: Vec<u8>, ui: Box<dyn UI>, options: Options) -> Zmachine {
+        let checksum = Zmachine::calculate_checksum(&data);
         let memory = Buffer::new(data);
 
         let version = memory.read_byte(0x00);
@@ -176,6 +243,7 @@ impl Zmachine {
             save_name: format!("{}.sav", &options.save_name),
             instr_log: String::new(),
             original_dynamic: memory.slice(0, static_start).to_vec(),
+            checksum,
             globals_addr: memory.read_word(0x0C) as usize,
             routine_offset: memory.read_word(0x28) as usize,
             string_offset: memory.read_word(0x2A) as usize,
@@ -184,44 +252,199 @@ impl Zmachine {
             pc: initial_pc,
             frames: vec![Frame::empty()],
             alphabet,
+            unicode_table: Zmachine::load_unicode_table(&memory),
             abbrev_table: memory.read_word(0x18) as usize,
-            separators: Vec::new(),
             dictionary: HashMap::new(),
             prop_defaults,
             obj_table_addr: prop_defaults + (if version <= 3 { 31 } else { 63 }) * 2,
             obj_size: if version <= 3 { 9 } else { 14 },
             attr_width: if version <= 3 { 4 } else { 6 },
             paused_instr: None,
+            paused_args: Vec::new(),
+            window: 0,
+            style: Style::default(),
+            font: 1,
+            fixed_pitch: false,
+            screen_stream: true,
+            screen_printed: false,
+            memory_streams: Vec::new(),
+            transcript: None,
+            command_record: None,
+            command_script: VecDeque::new(),
             current_state: None,
             undos: Vec::new(),
             redos: Vec::new(),
+            undo_saves: VecDeque::new(),
+            save_chunks: Vec::new(),
+            resources: None,
+            loaded_sounds: HashSet::new(),
+            sound_routines: HashMap::new(),
+            finished_sounds: VecDeque::new(),
             rng: rand::SeedableRng::from_seed(options.rand_seed.clone()),
+            fault: Cell::new(None),
+            warned: HashSet::new(),
             memory,
             options,
         };
 
+        // the UI needs the game's extra characters to read them as keys
+        zvm.ui.set_unicode_table(&zvm.unicode_table);
+
         // read into dictionary & word separators
         zvm.populate_dictionary();
+        zvm.write_header();
+
+        if !zvm.options.command_file.is_empty() {
+            let path = PathBuf::from(&zvm.options.command_file);
+            zvm.load_command_file(&path);
+        }
 
         zvm
     }
 
-    #[allow(dead_code)]
-    fn calculate_checksum(memory: &Buffer) -> u16 {
-        let mut sum: usize = 0;
-        let len = memory.read_byte(0x1A) as usize * 2;
+    // Pictures, sounds etc. from a blorb file the game was loaded from
+    pub fn set_resources(&mut self, resources: Blorb) {
+        self.resources = Some(resources);
+        self.write_header();
+    }
+
+    // The interpreter owns a handful of header fields: which features the UI
+    // has, the screen size & default colours, and the standard revision.
+    // Games can overwrite these, so they're re-applied on restart/restore.
+    fn write_header(&mut self) {
+        let mut flags = self.memory.read_byte(0x01);
+        let styles = self.ui.supports_styles();
+        let colours = self.ui.supports_colours() && self.version >= 5;
+        let sound = self.ui.supports_sound() && self.has_sounds();
+
+        let mut set = |bit: u8, on: bool| {
+            if on {
+                flags |= 1 << bit;
+            } else {
+                flags &= !(1 << bit);
+            }
+        };
+
+        if self.version <= 3 {
+            set(4, false); // status line is available
+            set(5, true); // screen splitting
+            set(6, false); // default font isn't variable-pitch
+        } else {
+            set(0, colours);
+            set(1, false); // pictures
+            set(2, styles);
+            set(3, styles);
+            set(4, true); // fixed-space font
+            set(5, sound); // sound effects
+            set(7, true); // timed input
+        }
+
+        self.memory.write_byte(0x01, flags);
+
+        // Flags 2: games that want sound effects get told if they can't
+        if !sound {
+            let flags = self.memory.read_word(0x10);
+            self.memory.write_word(0x10, flags & !0b1000_0000);
+        }
+
+        // Flags 2: undo is always available (save_undo/restore_undo)
+        if self.version >= 5 {
+            let flags = self.memory.read_word(0x10);
+            self.memory.write_word(0x10, flags | 0b1_0000);
+        }
+
+        if self.version >= 4 {
+            let (lines, columns) = self.ui.get_screen_size();
+
+            self.memory.write_byte(0x1E, INTERPRETER_NUMBER);
+            self.memory.write_byte(0x1F, INTERPRETER_VERSION);
+            self.memory.write_byte(0x20, cmp::min(lines, 255) as u8);
+            self.memory.write_byte(0x21, cmp::min(columns, 255) as u8);
+        }
+
+        // screen units are just characters, so fonts are 1x1
+        if self.version >= 5 {
+            let (lines, columns) = self.ui.get_screen_size();
+            let (foreground, background) = self.ui.get_default_colours();
+
+            self.memory.write_word(0x22, columns);
+            self.memory.write_word(0x24, lines);
+            self.memory.write_byte(0x26, 1);
+            self.memory.write_byte(0x27, 1);
+            self.memory.write_byte(0x2C, background);
+            self.memory.write_byte(0x2D, foreground);
+        }
+
+        self.memory.write_byte(0x32, STANDARD_REVISION.0);
+        self.memory.write_byte(0x33, STANDARD_REVISION.1);
+    }
+
+    // Swaps in new dynamic memory (restart/restore), keeping the transcript
+    // & fixed-pitch bits of Flags 2 and the interpreter's header fields
+    fn replace_dynamic_memory(&mut self, data: &[u8]) {
+        let preserved = self.memory.read_word(0x10) & 0b11;
+
+        self.memory.write(0, data);
+        self.write_header();
+
+        let flags = self.memory.read_word(0x10) & !0b11;
+        self.memory.write_word(0x10, flags | preserved);
+    }
+
+    // The header's file length (0x1A) is scaled by a version-dependent
+    // factor. Very early games might not set it, so use the whole file then.
+    pub fn file_length(data: &[u8]) -> usize {
+        let scale = match data[0] {
+            1..=3 => 2,
+            4 | 5 => 4,
+            _ => 8,
+        };
+        let length = ((data[0x1A] as usize) << 8 | data[0x1B] as usize) * scale;
 
-        for i in 0x40..len {
-            sum += memory.read_byte(i) as usize;
+        if length == 0 {
+            data.len()
+        } else {
+            length
         }
+    }
+
+    // Checksum of the (unmodified) story file: the sum of every byte after
+    // the header up to the file length, mod 0x10000. Compare it to 0x1C.
+    pub fn calculate_checksum(data: &[u8]) -> u16 {
+        let end = cmp::min(Zmachine::file_length(data), data.len());
 
-        (sum % 0x1_0000) as u16
+        data.iter()
+            .take(end)
+            .skip(0x40)
+            .fold(0u16, |sum, byte| sum.wrapping_add(u16::from(*byte)))
     }
 
     fn to_alphabet_entry(s: &str) -> Vec<String> {
         s.chars().map(|c| c.to_string()).collect()
     }
 
+    // The header extension table can point to a unicode translation table
+    // (a count byte, then that many words) for zscii 155 and up
+    fn load_unicode_table(memory: &Buffer) -> Vec<char> {
+        let extension = memory.read_word(0x36) as usize;
+
+        if extension == 0 || memory.read_word(extension) < 3 {
+            return DEFAULT_UNICODE.to_vec();
+        }
+
+        let table = memory.read_word(extension + 6) as usize;
+        if table == 0 {
+            return DEFAULT_UNICODE.to_vec();
+        }
+
+        let count = memory.read_byte(table) as usize;
+
+        (0..count)
+            .map(|n| memory.read_word(table + 1 + n * 2))
+            .map(|code| char::from_u32(u32::from(code)).unwrap_or('?'))
+            .collect()
+    }
+
     #[allow(non_snake_case)]
     fn default_alphabet() -> [Vec<String>; 3] {
         let A0 = " .....abcdefghijklmnopqrstuvwxyz";
@@ -255,6 +478,8 @@ impl Zmachine {
         }
     }
 
+    // Packed addresses scale by version, and v6/v7 add an offset (in units
+    // of 8 bytes) from the header for routines & strings (spec 1.2.3)
     fn unpack(&self, addr: u16) -> usize {
         let addr = addr as usize;
 
@@ -267,16 +492,16 @@ impl Zmachine {
     }
 
     fn unpack_routine_addr(&self, addr: u16) -> usize {
-        match self.unpack(addr) {
-            x @ 6..=7 => x + self.routine_offset * 8,
-            x => x,
+        match self.version {
+            6 | 7 => self.unpack(addr) + self.routine_offset * 8,
+            _ => self.unpack(addr),
         }
     }
 
     fn unpack_print_paddr(&self, addr: u16) -> usize {
-        match self.unpack(addr) {
-            x @ 6..=7 => x + self.string_offset * 8,
-            x => x,
+        match self.version {
+            6 | 7 => self.unpack(addr) + self.string_offset * 8,
+            _ => self.unpack(addr),
         }
     }
 
@@ -298,39 +523,128 @@ impl Zmachine {
         self.memory.write_word(addr, value);
     }
 
+    // Problems in the middle of an instruction (like popping an empty stack)
+    // don't stop it straight away. They get recorded here, a placeholder
+    // value gets used and the error comes out once the instruction is done.
+    fn fault(&self, cause: Cause) {
+        let first = self.fault.take().unwrap_or(cause);
+        self.fault.set(Some(first));
+    }
+
+    fn take_fault(&self) -> Option<Cause> {
+        let memory = self.memory.take_fault().map(Cause::OutOfBounds);
+        self.fault.take().or(memory)
+    }
+
+    // (without clearing it, so the instruction can stop short)
+    fn has_fault(&self) -> bool {
+        let cause = self.fault.take();
+        let faulted = cause.is_some();
+        self.fault.set(cause);
+
+        faulted || self.memory.has_fault()
+    }
+
+    // A game did something the spec doesn't allow. Depending on the error
+    // level that's either fatal, or gets reported (and the caller carries on
+    // with a fallback). `site` names the check, and along with the pc decides
+    // whether it's been warned about already.
+    fn violation(&mut self, site: &'static str, msg: &str) {
+        let level = self.options.error_level;
+
+        if level == ErrorLevel::Fatal {
+            self.fault(Cause::Violation(msg.to_string()));
+            return;
+        }
+
+        let first = self.warned.insert((site, self.pc));
+
+        if level == ErrorLevel::WarnAlways || (level == ErrorLevel::WarnOnce && first) {
+            self.ui.debug(&format!("\n[Warning: {} (pc {:#06x})]\n", msg, self.pc));
+        }
+    }
+
+    // object 0 means "nothing", it can't be used like a real object
+    fn check_object(&mut self, site: &'static str, object: u16) -> bool {
+        if object == 0 {
+            self.violation(site, &format!("@{} called with object 0", site));
+        }
+
+        object != 0
+    }
+
+    fn check_attr(&mut self, site: &'static str, object: u16, attr: u16) -> bool {
+        if !self.check_object(site, object) {
+            return false;
+        }
+
+        if attr as usize >= self.attr_width * 8 {
+            self.violation(site, &format!("@{} with out of range attribute {}", site, attr));
+            return false;
+        }
+
+        true
+    }
+
+    fn check_fault(&self, instr: &Instruction) -> Result<(), ZmachineError> {
+        match self.take_fault() {
+            Some(cause) => Err(ZmachineError::new(instr.addr, Some(instr.opcode), cause)),
+            None => Ok(()),
+        }
+    }
+
     fn read_local(&self, index: u8) -> u16 {
-        self.frames
-            .last()
-            .expect("Can't write local, no frames!")
-            .read_local(index)
+        let result = match self.frames.last() {
+            Some(frame) => frame.read_local(index),
+            None => Err(Cause::NoFrames),
+        };
+
+        result.unwrap_or_else(|cause| {
+            self.fault(cause);
+            0
+        })
     }
 
     fn write_local(&mut self, index: u8, value: u16) {
-        self.frames
-            .last_mut()
-            .expect("Can't write local, no frames!")
-            .write_local(index, value);
+        let result = match self.frames.last_mut() {
+            Some(frame) => frame.write_local(index, value),
+            None => Err(Cause::NoFrames),
+        };
+
+        if let Err(cause) = result {
+            self.fault(cause);
+        }
     }
 
     fn stack_push(&mut self, value: u16) {
-        self.frames
-            .last_mut()
-            .expect("Can't push to stack, no frames!")
-            .stack_push(value);
+        match self.frames.last_mut() {
+            Some(frame) => frame.stack_push(value),
+            None => self.fault(Cause::NoFrames),
+        }
     }
 
     fn stack_pop(&mut self) -> u16 {
-        self.frames
-            .last_mut()
-            .expect("Can't pop stack, no frames!")
-            .stack_pop()
+        let result = match self.frames.last_mut() {
+            Some(frame) => frame.stack_pop(),
+            None => Err(Cause::NoFrames),
+        };
+
+        result.unwrap_or_else(|cause| {
+            self.fault(cause);
+            0
+        })
     }
 
     fn stack_peek(&mut self) -> u16 {
-        self.frames
-            .last_mut()
-            .expect("Can't peek stack, no frames!")
-            .stack_peek()
+        let result = match self.frames.last() {
+            Some(frame) => frame.stack_peek(),
+            None => Err(Cause::NoFrames),
+        };
+
+        result.unwrap_or_else(|cause| {
+            self.fault(cause);
+            0
+        })
     }
 
     fn read_variable(&mut self, index: u8) -> u16 {
@@ -420,8 +734,12 @@ impl Zmachine {
                     (6, &Alphabet(2)) => Tenbit1,
                     (_, &Tenbit1) => Tenbit2(zchar),
                     (_, &Tenbit2(first)) => {
-                        let letter = ((first << 5) + zchar) as char;
-                        zstring.push_str(&letter.to_string());
+                        let zscii = (u16::from(first) << 5) + u16::from(zchar);
+
+                        if let Some(letter) = zscii_to_char(zscii, &self.unicode_table) {
+                            zstring.push(letter);
+                        }
+
                         Alphabet(0)
                     }
                     // get the abbrev at this addr
@@ -449,8 +767,8 @@ impl Zmachine {
                 step(((word >> 5) & 0b0001_1111) as u8);
                 step((word & 0b0001_1111) as u8);
 
-                // stop bit
-                if word & 0x8000 != 0 {
+                // stop bit (or running off the end of memory)
+                if word & 0x8000 != 0 || index >= self.memory.len() {
                     break;
                 }
             }
@@ -468,8 +786,8 @@ impl Zmachine {
             let word = self.memory.read_word(addr + length);
             length += 2;
 
-            // stop bit
-            if word & 0x8000 != 0 {
+            // stop bit (or running off the end of memory)
+            if word & 0x8000 != 0 || addr + length >= self.memory.len() {
                 break;
             }
         }
@@ -479,13 +797,8 @@ impl Zmachine {
 
     fn populate_dictionary(&mut self) {
         let dictionary_start = self.memory.read_word(0x08) as usize;
-        let mut read = self.memory.get_reader(dictionary_start);
-
-        let separator_count = read.byte();
-
-        for _ in 0..separator_count {
-            self.separators.push(read.byte() as char);
-        }
+        let separator_count = self.memory.read_byte(dictionary_start) as usize;
+        let mut read = self.memory.get_reader(dictionary_start + 1 + separator_count);
 
         let entry_length = read.byte() as usize;
         let entry_count = read.word() as usize;
@@ -499,51 +812,131 @@ impl Zmachine {
         }
     }
 
-    fn check_dict(&self, word: &str) -> usize {
+    // word separators are listed at the start of each dictionary
+    fn dictionary_separators(&self, dict_addr: usize) -> Vec<char> {
+        let count = self.memory.read_byte(dict_addr) as usize;
+
+        self.memory
+            .read(dict_addr + 1, count)
+            .iter()
+            .filter_map(|&zscii| zscii_to_char(u16::from(zscii), &self.unicode_table))
+            .collect()
+    }
+
+    // Finds a word's entry in the dictionary at `dict_addr` (0 if it's not
+    // there) by comparing encoded text. Game-supplied dictionaries can have
+    // a negative entry count, which means they aren't sorted.
+    fn check_dict(&self, dict_addr: usize, word: &str) -> usize {
+        let encoded = self.encode_zstring(word);
+        let separators = self.memory.read_byte(dict_addr) as usize;
+
+        let mut read = self.memory.get_reader(dict_addr + 1 + separators);
+        let entry_length = read.byte() as usize;
+        let entry_count = (read.word() as i16).unsigned_abs() as usize;
+        let entry_start = read.position();
+
+        (0..entry_count)
+            .map(|n| entry_start + n * entry_length)
+            .find(|&addr| self.memory.read(addr, encoded.len()) == &encoded[..])
+            .unwrap_or(0)
+    }
+
+    // Encodes text the way dictionary words are stored: cut off or padded
+    // to 6 zchars (v1-3) or 9 (v4+), with the stop bit on the last word
+    fn encode_zstring(&self, text: &str) -> Vec<u8> {
         let length = if self.version <= 3 { 6 } else { 9 };
-        let mut short = word.to_string();
-        short.truncate(length);
+        let mut zchars = Vec::new();
+
+        // the first few zchars in each alphabet are special (shifts, etc.)
+        let find = |alphabet: usize, skip: usize, letter: &str| {
+            self.alphabet[alphabet]
+                .iter()
+                .skip(skip)
+                .position(|chr| chr == letter)
+                .map(|index| (index + skip) as u8)
+        };
+
+        for chr in text.chars() {
+            let letter = chr.to_string();
+
+            if chr == ' ' {
+                zchars.push(0);
+            } else if let Some(zchar) = find(0, 6, &letter) {
+                zchars.push(zchar);
+            } else if let Some(zchar) = find(1, 6, &letter) {
+                zchars.extend(&[4, zchar]);
+            } else if let Some(zchar) = find(2, 7, &letter) {
+                zchars.extend(&[5, zchar]);
+            } else if let Some(zscii) = char_to_zscii(chr, &self.unicode_table) {
+                // 10bit zscii: A2 escape, then the top & bottom 5 bits
+                zchars.extend(&[5, 6, (zscii >> 5) as u8, (zscii & 0x1F) as u8]);
+            }
+        }
+
+        zchars.truncate(length);
+        zchars.resize(length, 5);
+
+        let mut bytes = Vec::new();
+
+        for (i, chunk) in zchars.chunks(3).enumerate() {
+            let mut word = (u16::from(chunk[0]) << 10) | (u16::from(chunk[1]) << 5) | u16::from(chunk[2]);
 
-        match self.dictionary.get(&short) {
-            Some(addr) => *addr,
-            None => 0,
+            if i == length / 3 - 1 {
+                word |= 0x8000;
+            }
+
+            bytes.push((word >> 8) as u8);
+            bytes.push((word & 0xFF) as u8);
         }
+
+        bytes
     }
 
-    fn tokenise(&mut self, text: &str, parse_addr: usize) {
+    // Splits text into words, writing them to the parse buffer along with
+    // their dictionary entries. If `keep_unknown` is set, the parse buffer
+    // slots for words that aren't in the dictionary are left as they were.
+    fn tokenise(&mut self, text: &str, parse_addr: usize, dict_addr: usize, keep_unknown: bool) {
         // v1-4 start storing @ byte 1, v5+ start @2;
         let start = if self.version <= 4 { 1 } else { 2 };
         let mut input = String::from(text);
         let mut found = HashMap::new();
 
-        for sep in &self.separators {
+        for sep in self.dictionary_separators(dict_addr) {
             input = input.replace(&sep.to_string(), &format!(" {} ", sep))
         }
 
+        let max_tokens = self.memory.read_byte(parse_addr) as usize;
+
         let tokens: Vec<_> = input
             .split_whitespace()
             .filter(|token| !token.is_empty())
+            .take(max_tokens)
             .map(|token| {
                 let offset = found.entry(token).or_insert(0);
                 let position = text[*offset..].find(token).unwrap();
 
-                let dict_addr = self.check_dict(token);
-                let token_addr = *offset + position + start;
+                // each character is one zscii byte in the text buffer
+                let dict_addr = self.check_dict(dict_addr, token);
+                let token_addr = text[..*offset + position].chars().count() + start;
 
                 *offset += position + token.len();
 
-                (dict_addr, token.len(), token_addr)
+                (dict_addr, token.chars().count(), token_addr)
             })
             .collect();
 
-        let mut write = self.memory.get_writer(parse_addr + 1);
-        write.byte(tokens.len() as u8);
+        self.memory.write_byte(parse_addr + 1, tokens.len() as u8);
 
-        tokens.iter().for_each(|&(dict_addr, len, token_addr)| {
+        for (n, &(dict_addr, len, token_addr)) in tokens.iter().enumerate() {
+            if keep_unknown && dict_addr == 0 {
+                continue;
+            }
+
+            let mut write = self.memory.get_writer(parse_addr + 2 + n * 4);
             write.word(dict_addr as u16);
             write.byte(len as u8);
             write.byte(token_addr as u8);
-        });
+        }
     }
 
     fn get_object_addr(&self, object: u16) -> usize {
@@ -713,10 +1106,9 @@ impl Zmachine {
         // by convention, the property table for object #1 is located AFTER
         // the last object in the object table:
         let obj_table_end = self.get_object_prop_table_addr(1);
-        let obj_size = self.attr_width + if self.version <= 3 { 3 } else { 9 } + 2;
 
         // v1-3 have a max of 255 objects, v4+ can have up to 65535
-        ((obj_table_end - self.obj_table_addr) / obj_size) as u16
+        ((obj_table_end - self.obj_table_addr) / self.obj_size) as u16
     }
 
     fn add_object_children(&self, parent: &mut Object) {
@@ -773,8 +1165,8 @@ impl Zmachine {
     }
 
     fn test_attr(&self, object: u16, attr: u16) -> u16 {
-        if attr as usize > self.attr_width * 8 {
-            panic!("Can't test out-of-bounds attribute: {}", attr);
+        if attr as usize >= self.attr_width * 8 {
+            return 0;
         }
 
         let addr = self.get_object_addr(object) + attr as usize / 8;
@@ -785,8 +1177,8 @@ impl Zmachine {
     }
 
     fn set_attr(&mut self, object: u16, attr: u16) {
-        if attr as usize > self.attr_width * 8 {
-            panic!("Can't set out-of-bounds attribute: {}", attr);
+        if attr as usize >= self.attr_width * 8 {
+            return;
         }
 
         let addr = self.get_object_addr(object) + attr as usize / 8;
@@ -797,8 +1189,8 @@ impl Zmachine {
     }
 
     fn clear_attr(&mut self, object: u16, attr: u16) {
-        if attr as usize > self.attr_width * 8 {
-            panic!("Can't clear out-of-bounds attribute: {}", attr);
+        if attr as usize >= self.attr_width * 8 {
+            return;
         }
 
         let addr = self.get_object_addr(object) + attr as usize / 8;
@@ -950,6 +1342,26 @@ impl Zmachine {
         (num, name)
     }
 
+    // Terminal UI only (for the save inspector)
+    #[allow(dead_code)]
+    pub fn object_name(&self, object: u16) -> String {
+        self.get_object_name(object)
+    }
+
+    // Terminal UI only (for the save inspector)
+    #[allow(dead_code)]
+    pub fn layout(&self) -> Layout {
+        Layout {
+            globals: self.globals_addr,
+            prop_defaults: self.prop_defaults,
+            objects: self.obj_table_addr,
+            object_count: self.get_total_object_count() as usize,
+            obj_size: self.obj_size,
+            attr_width: self.attr_width,
+            small_objects: self.version <= 3,
+        }
+    }
+
     fn get_status(&self) -> (String, String) {
         let num = self.read_global(0);
         let left = self.get_object_name(num);
@@ -986,165 +1398,541 @@ impl Zmachine {
         self.ui.set_status_bar(&left, &right);
     }
 
-    fn make_save_state(&self, pc: usize) -> Vec<u8> {
-        // save the whole dynamic memory region (between 0 and the start of static)
-        let dynamic = self.memory.slice(0, self.static_start);
-        let original = self.original_dynamic.as_slice();
-        let frames = &self.frames;
-        let chksum = self.memory.read_word(0x1c);
-        let release = self.memory.read_word(0x02);
-        let serial = self.memory.read(0x12, 6);
+    // All game text goes through the output streams: stream 3 (memory) takes
+    // everything while it's selected, otherwise it goes to the screen and
+    // (for lower window text) the transcript
+    fn print(&mut self, text: &str) {
+        self.print_to_streams(text, false);
+    }
 
-        QuetzalSave::make(pc, dynamic, original, frames, chksum, release, serial)
+    fn print_object(&mut self, name: &str) {
+        self.print_to_streams(name, true);
     }
 
-    fn restore_state(&mut self, data: &[u8]) {
-        let save = QuetzalSave::from_bytes(&data[..], &self.original_dynamic[..]);
+    fn print_to_streams(&mut self, text: &str, is_object: bool) {
+        if let Some((table, written)) = self.memory_streams.pop() {
+            let mut count = written;
+
+            for chr in text.chars() {
+                let zscii = char_to_zscii(chr, &self.unicode_table).unwrap_or(u16::from(b'?'));
+
+                self.memory.write_byte(table + 2 + count, zscii as u8);
+                count += 1;
+            }
 
-        // verify that the save if so the right game and that the memory is ok
-        if save.chksum != self.memory.read_word(0x1C) {
-            panic!("Invalid save, checksum is different");
+            self.memory_streams.push((table, count));
+            return;
         }
 
-        if self.static_start < save.memory.len() {
-            panic!("Invalid save, memory is too long");
+        // there's no native character graphics font, so swap in unicode
+        let graphics;
+        let text = if self.font == 3 {
+            graphics = text.chars().map(Zmachine::font3_char).collect::<String>();
+            &graphics[..]
+        } else {
+            text
+        };
+
+        if self.screen_stream {
+            self.screen_printed = true;
+            self.sync_fixed_pitch();
+
+            if is_object {
+                self.ui.print_object(text);
+            } else {
+                self.ui.print(text);
+            }
         }
 
-        self.pc = save.pc;
-        self.frames = save.frames;
-        self.memory.write(0, save.memory.as_slice());
-    }
+        self.sync_transcript();
 
-    pub fn undo(&mut self) -> bool {
-        if let Some(ref instr) = self.paused_instr {
-            if instr.opcode != Opcode::VAR_228 {
-                return false;
+        if self.window == 0 {
+            if let Some(ref mut file) = self.transcript {
+                // (a transcript that can't be written to isn't worth stopping for)
+                file.write_all(text.as_bytes()).ok();
             }
         }
+    }
 
-        if self.undos.is_empty() {
-            self.ui.print("\n[Can't undo that far.]\n");
-            return false;
+    fn font3_char(chr: char) -> char {
+        match chr {
+            ' '..='~' => FONT3[chr as usize - 32],
+            _ => chr,
         }
+    }
 
-        let new_current = self.undos.pop().unwrap();
-        self.redos.push(self.current_state.take().unwrap());
+    // The UI gets the game's style, plus fixed-pitch for the fixed & graphics
+    // fonts, or when the game asks for it with bit 1 of Flags 2
+    fn apply_style(&mut self) {
+        let mut style = self.style;
+        style.fixed |= self.fixed_pitch || self.font == 3 || self.font == 4;
 
-        self.restore_state(new_current.1.as_slice());
-        self.current_state = Some(new_current);
+        self.ui.set_style(style);
+    }
 
-        true
+    fn sync_fixed_pitch(&mut self) {
+        let forced = self.memory.read_word(0x10) & 0b10 != 0;
+
+        if forced != self.fixed_pitch {
+            self.fixed_pitch = forced;
+            self.apply_style();
+        }
     }
 
-    pub fn redo(&mut self) -> bool {
-        if let Some(ref instr) = self.paused_instr {
-            if instr.opcode != Opcode::VAR_228 {
-                return false;
+    // The game can also start/stop a transcript by flipping bit 0 of Flags 2
+    fn sync_transcript(&mut self) {
+        let enabled = self.memory.read_word(0x10) & 1 != 0;
+
+        if enabled && self.transcript.is_none() {
+            self.transcript = self.open_stream_file("scr");
+
+            // couldn't open a file, let the game know it isn't transcripting
+            if self.transcript.is_none() {
+                let flags = self.memory.read_word(0x10);
+                self.memory.write_word(0x10, flags & !1);
             }
+        } else if !enabled && self.transcript.is_some() {
+            self.transcript = None;
         }
+    }
 
-        if self.redos.is_empty() {
-            self.ui.print("\n[Nothing to redo.]\n");
-            return false;
-        }
+    // transcripts & command records are named after the story file, like saves
+    fn stream_file_path(&self, extension: &str) -> PathBuf {
+        let mut path = PathBuf::from(&self.save_dir);
+        path.push(format!("{}.{}", self.options.save_name, extension));
+        path
+    }
 
-        let new_current = self.redos.pop().unwrap();
-        self.undos.push(self.current_state.take().unwrap());
+    // Asks the player for a file name in the save directory (None = cancel)
+    fn prompt_file_path(&mut self, default: &str) -> Option<PathBuf> {
+        let prompt = format!("\nFilename [{}]: ", default);
+        self.ui.print(&prompt);
 
-        self.restore_state(new_current.1.as_slice());
-        self.current_state = Some(new_current);
+        let input = self.ui.get_user_input();
+        let mut path = PathBuf::from(&self.save_dir);
 
-        true
-    }
+        match input.to_lowercase().as_ref() {
+            "" | "yes" | "y" => path.push(default),
+            "no" | "n" | "cancel" => return None,
+            _ => path.push(input),
+        }
 
-    fn get_arguments(&mut self, operands: &[Operand]) -> Vec<u16> {
-        operands
-            .iter()
-            .map(|operand| match *operand {
-                Operand::Small(val) => u16::from(val),
-                Operand::Large(val) => val,
-                Operand::Variable(val) => self.read_variable(val),
-            })
-            .collect()
+        Some(path)
     }
 
-    fn return_from_routine(&mut self, value: u16) {
-        let frame = self.frames.pop().expect("Can't pop off last frame!");
-        self.pc = frame.resume;
+    // Auxiliary files (from save/restore with a table) are named by the game
+    // with a length-prefixed string, defaulting to the story name
+    fn aux_file_name(&self, addr: u16) -> String {
+        let name = if addr == 0 {
+            String::new()
+        } else {
+            let len = self.memory.read_byte(addr as usize) as usize;
+
+            self.memory
+                .read(addr as usize + 1, len)
+                .iter()
+                .map(|&byte| (byte as char).to_ascii_lowercase())
+                .filter(|chr| chr.is_ascii_alphanumeric() || "._-".contains(*chr))
+                .collect()
+        };
 
-        if let Some(index) = frame.store {
-            self.write_variable(index, value);
+        match name.as_ref() {
+            "" => format!("{}.aux", self.options.save_name),
+            _ if name.contains('.') => name,
+            _ => format!("{}.aux", name),
         }
     }
 
-    fn process_branch(&mut self, branch: &Branch, next: usize, result: u16) {
-        let Branch {
-            address,
-            returns,
-            condition,
-        } = *branch;
-        let result = if result >= 1 { 1 } else { 0 };
+    fn aux_file_path(&mut self, name: &str, prompt: bool) -> Option<PathBuf> {
+        if prompt {
+            self.prompt_file_path(name)
+        } else {
+            Some(PathBuf::from(&self.save_dir).join(name))
+        }
+    }
 
-        if let Some(index) = address {
-            self.pc = if result == condition { index } else { next };
+    fn open_stream_file(&mut self, extension: &str) -> Option<File> {
+        File::create(self.stream_file_path(extension)).ok()
+    }
+
+    // Input stream 1: a file of commands, one per line, that get played back
+    // before going back to the keyboard
+    fn load_command_file(&mut self, path: &Path) -> bool {
+        let mut contents = String::new();
+        let read = File::open(path).and_then(|mut file| file.read_to_string(&mut contents));
+
+        if read.is_err() {
+            return false;
         }
 
-        if let Some(value) = returns {
-            if result == condition {
-                self.return_from_routine(value);
-            } else {
-                self.pc = next
+        self.command_script = contents.lines().map(String::from).collect();
+        true
+    }
+
+    // Gets the next line of input from the command file if one is being
+    // played back (echoing it, since nobody typed it), or from the keyboard
+    fn read_command(&mut self) -> String {
+        match self.command_script.pop_front() {
+            Some(line) => {
+                self.ui.print(&line);
+                self.ui.print("\n");
+                line
             }
+            None => self.ui.get_user_input(),
         }
     }
 
-    fn process_result(&mut self, instr: &Instruction, value: u16) {
-        // store the result if needed
-        if let Some(index) = instr.store {
-            self.write_variable(index, value);
+    // player input gets echoed to the transcript and logged to stream 4
+    fn record_input(&mut self, input: &str) {
+        self.sync_transcript();
+
+        if let Some(ref mut file) = self.transcript {
+            writeln!(file, "{}", input).ok();
         }
 
-        // check if we need to branch
-        if let Some(ref branch) = instr.branch {
-            self.process_branch(branch, instr.next, value);
-        } else {
-            self.pc = instr.next;
+        if let Some(ref mut file) = self.command_record {
+            writeln!(file, "{}", input).ok();
         }
     }
 
-    fn decode_instruction(&self, addr: usize) -> Instruction {
-        let mut read = self.memory.get_reader(addr);
-        let first = read.byte();
-
-        let btm_4 = |num| num & 0b0000_1111;
-        let btm_5 = |num| num & 0b0001_1111;
-        let get_types = |bytes: &[u8]| OperandType::from(bytes);
+    // Reads a line like `read_command`, but starting from `input` (already on
+    // screen) and calling the interrupt routine every `time` tenths of a
+    // second (if given). Also finishes on any of the `terminators`. Returns
+    // the input and the key that ended it, which is 0 if the routine aborted
+    // the read (the input is then whatever was typed so far).
+    fn read_timed_command(
+        &mut self,
+        mut input: String,
+        time: u16,
+        routine: u16,
+        terminators: &[u16],
+    ) -> (String, u16) {
+        // the script line carries on from any preloaded text, just like
+        // typing would
+        if !self.command_script.is_empty() {
+            input.push_str(&self.read_command());
+            return (input, 13);
+        }
 
-        let get_opcode = |code: u8, offset: u16| {
-            let num = u16::from(code) + offset;
+        let mut redraw = false;
 
-            match Opcode::from_u16(num) {
-                Some(val) => val,
-                None => panic!("Opcode not found: {:?}", num),
+        loop {
+            if let Some(key) = self.ui.get_timed_input(&mut input, time, redraw, terminators) {
+                return (input, key);
             }
-        };
 
-        use self::OperandType::*;
+            self.screen_printed = false;
 
-        #[allow(unreachable_patterns)]
+            if self.call_interrupt(routine) != 0 {
+                return (input, 0);
+            }
+
+            // the partial input needs to be redrawn if the routine printed
+            redraw = self.screen_printed;
+        }
+    }
+
+    // v5+ games can list keys besides enter that finish line input, in a
+    // zero-terminated table at 0x2E. Only function keys are allowed, and 255
+    // means all of them.
+    fn terminating_keys(&self) -> Vec<u16> {
+        let addr = match self.version {
+            5..=8 => self.memory.read_word(0x2E) as usize,
+            _ => 0,
+        };
+
+        let mut keys = Vec::new();
+
+        if addr == 0 {
+            return keys;
+        }
+
+        let mut read = self.memory.get_reader(addr);
+
+        loop {
+            match read.byte() {
+                0 => break,
+                255 => keys.extend((129..=154).chain(252..=254)),
+                key @ 129..=154 | key @ 252..=254 => keys.push(u16::from(key)),
+                _ => (),
+            }
+        }
+
+        keys
+    }
+
+    // Reads a single key, calling the interrupt routine every `time` tenths
+    // of a second (if given). Returns 0 if the routine aborted the read.
+    fn read_timed_key(&mut self, time: u16, routine: u16) -> u16 {
+        loop {
+            if let Some(key) = self.ui.get_user_key(time) {
+                return key;
+            }
+
+            if self.call_interrupt(routine) != 0 {
+                return 0;
+            }
+        }
+    }
+
+    // Runs an interrupt routine to completion and returns its result. The
+    // routine's frame stores its result on the current stack, where it gets
+    // popped right back off. If the routine fails it gets abandoned (as if
+    // it returned 0) and the error is left as a fault for the caller.
+    fn call_interrupt(&mut self, addr: u16) -> u16 {
+        let pc = self.pc;
+        let depth = self.frames.len();
+
+        // hang on to anything the current instruction already ran into
+        let outer = self.take_fault();
+
+        match self.run_interrupt(addr) {
+            Ok(value) => {
+                if outer.is_some() {
+                    self.fault.set(outer);
+                }
+
+                value
+            }
+            Err(err) => {
+                self.frames.truncate(depth);
+                self.pc = pc;
+                self.fault.set(outer);
+                self.fault(Cause::Interrupt(Box::new(err)));
+                0
+            }
+        }
+    }
+
+    fn run_interrupt(&mut self, addr: u16) -> Result<u16, ZmachineError> {
+        let pc = self.pc;
+        let depth = self.frames.len();
+
+        self.push_routine(addr, &[], pc, Some(0));
+
+        while self.frames.len() > depth {
+            let instr = self.decode_instruction(self.pc)?;
+
+            // quitting isn't allowed here, treat it as ending the input
+            if instr.opcode == Opcode::OP0_186 {
+                self.frames.truncate(depth);
+                self.pc = pc;
+                return Ok(1);
+            }
+
+            self.handle_instruction(&instr)?;
+        }
+
+        self.pc = pc;
+        Ok(self.stack_pop())
+    }
+
+    // time/routine operands of a timed read or read_char (when both are set)
+    fn timed_args(opcode: Opcode, args: &[u16]) -> Option<(u16, u16)> {
+        let skip = if opcode == Opcode::VAR_228 { 2 } else { 1 };
+
+        match args.get(skip..) {
+            Some(&[time, routine, ..]) if time > 0 && routine > 0 => Some((time, routine)),
+            _ => None,
+        }
+    }
+
+    fn make_save_state(&self, pc: usize) -> Vec<u8> {
+        let mut serial = [0; 6];
+        for (byte, value) in serial.iter_mut().zip(self.memory.read(0x12, 6)) {
+            *byte = *value;
+        }
+
+        let save = QuetzalSave {
+            pc,
+            // save the whole dynamic memory region (between 0 and the start of static)
+            memory: self.memory.slice(0, self.static_start).to_vec(),
+            frames: self.frames.clone(),
+            release: self.memory.read_word(0x02),
+            serial,
+            chksum: self.memory.read_word(0x1c),
+            // anything another interpreter put in the save that was restored
+            chunks: self.save_chunks.clone(),
+        };
+
+        save.to_bytes(&self.original_dynamic, self.version, !self.options.save_umem)
+    }
+
+    pub fn restore_state(&mut self, data: &[u8]) -> Result<(), ZmachineError> {
+        let invalid = |msg: String| ZmachineError::new(self.pc, None, Cause::InvalidSave(msg));
+        let save = QuetzalSave::from_bytes(&data[..], &self.original_dynamic[..], self.version)
+            .map_err(&invalid)?;
+
+        // verify that the save is for the right game and that it's usable
+        let release = self.memory.read_word(0x02);
+        let serial = self.memory.read(0x12, 6);
+        let chksum = self.memory.read_word(0x1C);
+
+        save.check_story(release, serial, chksum).map_err(&invalid)?;
+
+        if save.pc >= self.memory.len() {
+            return Err(invalid(format!("pc {:#06x} is past the end of the game", save.pc)));
+        }
+
+        self.pc = save.pc;
+        self.frames = save.frames;
+        self.save_chunks = save.chunks;
+        self.replace_dynamic_memory(&save.memory);
+
+        Ok(())
+    }
+
+    pub fn undo(&mut self) -> bool {
+        if let Some(ref instr) = self.paused_instr {
+            if instr.opcode != Opcode::VAR_228 {
+                return false;
+            }
+        }
+
+        if self.undos.is_empty() {
+            self.ui.print("\n[Can't undo that far.]\n");
+            return false;
+        }
+
+        let new_current = self.undos.pop().unwrap();
+
+        if let Err(err) = self.restore_state(new_current.1.as_slice()) {
+            self.ui.debug(&format!("\n[Can't restore that state: {}]\n", err));
+            return false;
+        }
+
+        self.redos.extend(self.current_state.take());
+        self.current_state = Some(new_current);
+
+        true
+    }
+
+    pub fn redo(&mut self) -> bool {
+        if let Some(ref instr) = self.paused_instr {
+            if instr.opcode != Opcode::VAR_228 {
+                return false;
+            }
+        }
+
+        if self.redos.is_empty() {
+            self.ui.print("\n[Nothing to redo.]\n");
+            return false;
+        }
+
+        let new_current = self.redos.pop().unwrap();
+
+        if let Err(err) = self.restore_state(new_current.1.as_slice()) {
+            self.ui.debug(&format!("\n[Can't restore that state: {}]\n", err));
+            return false;
+        }
+
+        self.undos.extend(self.current_state.take());
+        self.current_state = Some(new_current);
+
+        true
+    }
+
+    fn get_arguments(&mut self, operands: &[Operand]) -> Vec<u16> {
+        operands
+            .iter()
+            .map(|operand| match *operand {
+                Operand::Small(val) => u16::from(val),
+                Operand::Large(val) => val,
+                Operand::Variable(val) => self.read_variable(val),
+            })
+            .collect()
+    }
+
+    fn return_from_routine(&mut self, value: u16) {
+        // the first frame is the main routine's, which can't be returned from
+        if self.frames.len() < 2 {
+            self.fault(Cause::NoFrames);
+            return;
+        }
+
+        let frame = self.frames.pop().unwrap();
+        self.pc = frame.resume;
+
+        if let Some(index) = frame.store {
+            self.write_variable(index, value);
+        }
+    }
+
+    fn process_branch(&mut self, branch: &Branch, next: usize, result: u16) {
+        let Branch {
+            address,
+            returns,
+            condition,
+        } = *branch;
+        let result = if result >= 1 { 1 } else { 0 };
+
+        if let Some(index) = address {
+            self.pc = if result == condition { index } else { next };
+        }
+
+        if let Some(value) = returns {
+            if result == condition {
+                self.return_from_routine(value);
+            } else {
+                self.pc = next
+            }
+        }
+    }
+
+    fn process_result(&mut self, instr: &Instruction, value: u16) {
+        // a faulting instruction shouldn't store or branch on its placeholder
+        if self.has_fault() {
+            return;
+        }
+
+        // store the result if needed
+        if let Some(index) = instr.store {
+            self.write_variable(index, value);
+        }
+
+        // check if we need to branch
+        if let Some(ref branch) = instr.branch {
+            self.process_branch(branch, instr.next, value);
+        } else {
+            self.pc = instr.next;
+        }
+    }
+
+    fn decode_instruction(&self, addr: usize) -> Result<Instruction, ZmachineError> {
+        if addr >= self.memory.len() {
+            return Err(ZmachineError::new(addr, None, Cause::OutOfBounds(addr)));
+        }
+
+        let mut read = self.memory.get_reader(addr);
+        let first = read.byte();
+
+        let btm_4 = |num| num & 0b0000_1111;
+        let btm_5 = |num| num & 0b0001_1111;
+        let get_types = |bytes: &[u8]| OperandType::from(bytes);
+
+        let get_opcode = |code: u8, offset: u16| {
+            let num = u16::from(code) + offset;
+
+            Opcode::from_u16(num)
+                .ok_or_else(|| ZmachineError::new(addr, None, Cause::UnknownOpcode(num)))
+        };
+
+        use self::OperandType::*;
+
+        #[allow(unreachable_patterns)]
         let (opcode, optypes) = match first {
-            0xbe => (get_opcode(read.byte(), 1000), get_types(&[read.byte()])),
-            0x00..=0x1f => (get_opcode(btm_5(first), 0), vec![Small, Small]),
-            0x20..=0x3f => (get_opcode(btm_5(first), 0), vec![Small, Variable]),
-            0x40..=0x5f => (get_opcode(btm_5(first), 0), vec![Variable, Small]),
-            0x60..=0x7f => (get_opcode(btm_5(first), 0), vec![Variable, Variable]),
-            0x80..=0x8f => (get_opcode(btm_4(first), 128), vec![Large]),
-            0x90..=0x9f => (get_opcode(btm_4(first), 128), vec![Small]),
-            0xa0..=0xaf => (get_opcode(btm_4(first), 128), vec![Variable]),
-            0xb0..=0xbd | 0xbf => (get_opcode(btm_4(first), 176), vec![]), // OP_0
-            0xc0..=0xdf => (get_opcode(btm_5(first), 0), get_types(&[read.byte()])),
+            0xbe => (get_opcode(read.byte(), 1000)?, get_types(&[read.byte()])),
+            0x00..=0x1f => (get_opcode(btm_5(first), 0)?, vec![Small, Small]),
+            0x20..=0x3f => (get_opcode(btm_5(first), 0)?, vec![Small, Variable]),
+            0x40..=0x5f => (get_opcode(btm_5(first), 0)?, vec![Variable, Small]),
+            0x60..=0x7f => (get_opcode(btm_5(first), 0)?, vec![Variable, Variable]),
+            0x80..=0x8f => (get_opcode(btm_4(first), 128)?, vec![Large]),
+            0x90..=0x9f => (get_opcode(btm_4(first), 128)?, vec![Small]),
+            0xa0..=0xaf => (get_opcode(btm_4(first), 128)?, vec![Variable]),
+            0xb0..=0xbd | 0xbf => (get_opcode(btm_4(first), 176)?, vec![]), // OP_0
+            0xc0..=0xdf => (get_opcode(btm_5(first), 0)?, get_types(&[read.byte()])),
             0xe0..=0xff => {
-                let opcode = get_opcode(btm_5(first), 224);
+                let opcode = get_opcode(btm_5(first), 224)?;
 
                 if opcode == Opcode::VAR_236 || opcode == Opcode::VAR_250 {
                     (opcode, get_types(&[read.byte(), read.byte()]))
@@ -1181,8 +1969,8 @@ impl Zmachine {
                 ((byte & 0b0011_1111) << 8) + read.byte() as usize
             };
 
-            // the offset (if two bytes) is a 14 bit unsigned int: 2^14 = 16384
-            let address = if offset > (16384 / 2) {
+            // the offset (if two bytes) is a 14 bit signed int: 2^14 = 16384
+            let address = if offset >= (16384 / 2) {
                 Some(read.position() + offset - 16384 - 2)
             } else {
                 Some(read.position() + offset - 2)
@@ -1224,7 +2012,13 @@ impl Zmachine {
         let name = Instruction::name(opcode, self.version);
         let next = read.position() + text_length;
 
-        Instruction {
+        // the instruction ran off the end of memory
+        if next > self.memory.len() {
+            let cause = Cause::OutOfBounds(self.memory.len());
+            return Err(ZmachineError::new(addr, Some(opcode), cause));
+        }
+
+        Ok(Instruction {
             addr,
             opcode,
             name,
@@ -1233,14 +2027,18 @@ impl Zmachine {
             branch,
             text,
             next,
-        }
+        })
     }
 
-    pub fn handle_instruction(&mut self, instr: &Instruction) {
+    pub fn handle_instruction(&mut self, instr: &Instruction) -> Result<(), ZmachineError> {
         use self::Opcode::*;
 
+        // drop anything left over from outside of an instruction
+        self.take_fault();
+
         // ~mutably~ gets the arguments (might pop stack)
         let args = self.get_arguments(instr.operands.as_slice());
+        self.check_fault(instr)?;
 
         if env::var("DEBUG").is_ok() {
             println!("\x1B[97m{}\x1B[0m", instr);
@@ -1276,14 +2074,21 @@ impl Zmachine {
             (OP1_132, &[addr]) => Some(self.do_get_prop_len(addr)),
             (OP1_142, &[var]) => Some(self.do_load(var)),
             (OP1_143, &[value]) if self.version <= 4 => Some(self.do_not(value)),
+            (OP0_185, &[]) if self.version >= 5 => Some(self.do_catch()),
             (OP0_189, &[]) => Some(self.do_verify()),
             (OP0_191, &[]) => Some(1), // piracy
             (VAR_231, &[range]) => Some(self.do_random(range)),
             (VAR_233, &[var]) if self.version == 6 => Some(self.do_pull(var)),
+            (VAR_246, _) if !args.is_empty() => Some(self.do_read_char(&args)),
+            (VAR_247, &[x, table, len]) => Some(self.do_scan_table(x, table, len, 0x82)),
+            (VAR_247, &[x, table, len, form]) => Some(self.do_scan_table(x, table, len, form)),
             (VAR_248, &[val]) if self.version >= 5 => Some(self.do_not(val)),
             (VAR_255, &[num]) => Some(self.do_check_arg_count(num)),
             (EXT_1002, &[num, places]) => Some(self.do_log_shift(num, places)),
             (EXT_1003, &[num, places]) => Some(self.do_art_shift(num, places)),
+            (EXT_1004, &[font]) => Some(self.do_set_font(font)),
+            (EXT_1009, &[]) => Some(self.do_save_undo(instr)),
+            (EXT_1012, &[chr]) => Some(self.do_check_unicode(chr)),
             _ => None,
         };
 
@@ -1292,7 +2097,7 @@ impl Zmachine {
         // Then return early since this instruction is done.
         if let Some(value) = result {
             self.process_result(instr, value);
-            return;
+            return self.check_fault(instr);
         }
 
         // All other instructions (don't produce a value, only a side effect)
@@ -1303,6 +2108,8 @@ impl Zmachine {
             (OP2_14, &[obj, dest]) => self.do_insert_obj(obj, dest),
             (OP2_25, &[addr, arg]) => self.do_call(instr, addr, &[arg]), // call_2s
             (OP2_26, &[addr, arg]) => self.do_call(instr, addr, &[arg]), // call_2n
+            (OP2_27, &[fg, bg]) => self.do_set_colour(fg, bg),
+            (OP2_28, &[value, frame]) => self.do_throw(value, frame),
             (OP1_133, &[var]) => self.do_inc(var),
             (OP1_134, &[var]) => self.do_dec(var),
             (OP1_135, &[addr]) => self.do_print_addr(addr),
@@ -1328,30 +2135,61 @@ impl Zmachine {
             (VAR_225, &[array, index, value]) => self.do_storew(array, index, value),
             (VAR_226, &[array, index, value]) => self.do_storeb(array, index, value),
             (VAR_227, &[obj, prop, value]) => self.do_put_prop(obj, prop, value),
-            (VAR_228, &[text, parse]) => self.do_sread(instr, text, parse),
+            (VAR_228, _) if !args.is_empty() => self.do_sread(instr, &args),
             (VAR_229, &[chr]) => self.do_print_char(chr),
             (VAR_230, &[num]) => self.do_print_num(num),
             (VAR_232, &[value]) => self.do_push(value),
             (VAR_233, &[var]) => { self.do_pull(var); }
+            (VAR_234, &[lines]) => self.do_split_window(lines),
+            (VAR_235, &[window]) => self.do_set_window(window),
+            (VAR_237, &[window]) => self.do_erase_window(window),
+            (VAR_238, &[value]) => self.do_erase_line(value),
+            (VAR_239, &[line, column]) => self.do_set_cursor(line, column),
+            (VAR_240, &[array]) => self.do_get_cursor(array),
+            (VAR_241, &[style]) => self.do_set_text_style(style),
+            (VAR_243, &[number]) => self.do_output_stream(number, 0),
+            (VAR_243, &[number, table]) => self.do_output_stream(number, table),
+            (VAR_243, &[number, table, _]) => self.do_output_stream(number, table),
             (VAR_236, _) if !args.is_empty() => self.do_call(instr, args[0], &args[1..]), // call_vs2
             (VAR_249, _) if !args.is_empty() => self.do_call(instr, args[0], &args[1..]), // call_vn
             (VAR_250, _) if !args.is_empty() => self.do_call(instr, args[0], &args[1..]), // call_vn2
+            (VAR_251, &[text, parse]) => self.do_tokenise(text, parse, 0, 0),
+            (VAR_251, &[text, parse, dict]) => self.do_tokenise(text, parse, dict, 0),
+            (VAR_251, &[text, parse, dict, flag]) => self.do_tokenise(text, parse, dict, flag),
+            (VAR_252, &[text, len, from, coded]) => self.do_encode_text(text, len, from, coded),
+            (VAR_253, &[first, second, size]) => self.do_copy_table(first, second, size),
+            (VAR_254, &[table, width]) => self.do_print_table(table, width, 1, 0),
+            (VAR_254, &[table, width, height]) => self.do_print_table(table, width, height, 0),
+            (VAR_254, &[table, width, height, skip]) => {
+                self.do_print_table(table, width, height, skip)
+            }
+            (EXT_1000, &[]) => self.do_save(instr),
+            (EXT_1000, _) if args.len() >= 2 => self.do_save_table(instr, &args),
+            (EXT_1001, &[]) => self.do_restore(instr),
+            (EXT_1001, _) if args.len() >= 2 => self.do_restore_table(instr, &args),
+            (EXT_1010, &[]) => self.do_restore_undo(instr),
+            (EXT_1011, &[chr]) => self.do_print_unicode(chr),
+            (EXT_1013, &[fg, bg]) => self.do_set_true_colour(fg, bg),
 
-            // special cases to no-op: (input/output streams & sound effects)
-            // these might be present in some v3 games but aren't implemented yet
-            (VAR_243, _) | (VAR_244, _) | (VAR_245, _) => (),
+            (VAR_244, &[number]) => self.do_input_stream(number),
 
-            _ => panic!(
-                "\n\nOpcode not yet implemented: {} ({:?}) @ {:#04x}\n\n",
-                instr.name, instr.opcode, self.pc
-            ),
+            (VAR_245, _) => self.do_sound_effect(&args),
+
+            _ => {
+                let cause = Cause::Unimplemented(instr.name.clone());
+                return Err(ZmachineError::new(instr.addr, Some(instr.opcode), cause));
+            }
         }
 
+        self.check_fault(instr)?;
+
         // advance pc to the next instruction
         // (but not for jumps, calls, save/restore, or anything with special needs)
         if instr.advances() && instr.should_advance(self.version) {
             self.pc = instr.next;
         }
+
+        Ok(())
     }
 
     fn is_debug_command(&self, input: &str) -> bool {
@@ -1458,31 +2296,52 @@ impl Zmachine {
 
     // Terminal UI only
     #[allow(dead_code)]
-    pub fn run(&mut self) {
+    pub fn run(&mut self) -> Result<(), ZmachineError> {
         self.ui.clear();
 
-        // continue instructions until the quit instruction
-        loop {
-            let instr = self.decode_instruction(self.pc);
+        // continue instructions until the quit instruction (or an error)
+        let result = loop {
+            let instr = match self.decode_instruction(self.pc) {
+                Ok(instr) => instr,
+                Err(err) => break Err(err),
+            };
 
             if instr.opcode == Opcode::OP0_186 {
-                break;
+                break Ok(());
             }
 
-            self.handle_instruction(&instr);
-        }
+            if let Err(err) = self.handle_instruction(&instr) {
+                break Err(err);
+            }
+        };
 
         self.ui.reset();
+        result
+    }
+
+    // Terminal UI only - writes out the state the zmachine was in when it
+    // failed (in the same format as a save) so it can be looked at later
+    #[allow(dead_code)]
+    pub fn save_crash_dump(&self, err: &ZmachineError) -> Option<PathBuf> {
+        let path = self.stream_file_path("crash");
+        let state = self.make_save_state(err.pc);
+
+        File::create(&path)
+            .and_then(|mut file| file.write_all(&state))
+            .ok()
+            .map(|_| path)
     }
 
     // Web UI only
     #[allow(dead_code)]
-    pub fn step(&mut self) -> bool {
+    pub fn step(&mut self) -> Result<bool, ZmachineError> {
         // loop through instructions until user input is needed
         // (saves/restores need a save name, read instructions need user input)
         // Pauses on these instructions and control is passed back to js
         loop {
-            let instr = self.decode_instruction(self.pc);
+            // (anything left over from outside of an instruction doesn't count)
+            self.take_fault();
+            let instr = self.decode_instruction(self.pc)?;
 
             if self.options.log_instructions {
                 write!(self.instr_log, "\n{}", &instr).unwrap();
@@ -1490,20 +2349,44 @@ impl Zmachine {
 
             match instr.opcode {
                 // SAVE
-                Opcode::OP0_181 => {
+                Opcode::OP0_181 | Opcode::EXT_1000 if instr.operands.is_empty() => {
                     let pc = instr.next - 1;
                     let state = self.make_save_state(pc);
                     self.send_save_message("save", &state);
 
                     // Advance the pc, assuming that the save was successful
                     self.process_save_result(&instr);
+                    self.check_fault(&instr)?;
                 }
                 // RESTORE (breaks loop)
-                Opcode::OP0_182 => {
+                Opcode::OP0_182 | Opcode::EXT_1001 if instr.operands.is_empty() => {
                     self.ui.message("restore", "");
                     self.paused_instr = Some(instr);
+                    self.run_sound_routines()?;
 
-                    return false;
+                    return Ok(false);
+                }
+                // SAVE (auxiliary file)
+                Opcode::EXT_1000 => {
+                    let args = self.get_arguments(instr.operands.as_slice());
+                    let name = self.aux_file_name(args.get(2).cloned().unwrap_or(0));
+                    let table = self.memory.read(args[0] as usize, args[1] as usize);
+                    let msg = (name, base64::encode(table));
+
+                    self.ui.message("save_aux", &serde_json::to_string(&msg).unwrap());
+                    self.process_result(&instr, 1);
+                    self.check_fault(&instr)?;
+                }
+                // RESTORE (auxiliary file, breaks loop)
+                Opcode::EXT_1001 => {
+                    self.paused_args = self.get_arguments(instr.operands.as_slice());
+                    let name = self.aux_file_name(self.paused_args.get(2).cloned().unwrap_or(0));
+
+                    self.ui.message("restore_aux", &name);
+                    self.paused_instr = Some(instr);
+                    self.run_sound_routines()?;
+
+                    return Ok(false);
                 }
                 // QUIT (breaks loop)
                 Opcode::OP0_186 => {
@@ -1515,7 +2398,7 @@ impl Zmachine {
                         self.send_save_message("savestate", &state);
                     }
 
-                    return true; // done == true
+                    return Ok(true); // done == true
                 }
                 // READ (breaks loop)
                 Opcode::VAR_228 => {
@@ -1525,34 +2408,125 @@ impl Zmachine {
                     // web ui saves current state here BEFORE processing user input
                     let (location, _) = self.get_status();
                     self.current_state = Some((location, state));
-                    self.paused_instr = Some(instr);
+                    self.pause_for_input(instr);
+                    self.run_sound_routines()?;
+
+                    return Ok(false);
+                }
+                // READ_CHAR (breaks loop)
+                Opcode::VAR_246 => {
+                    self.pause_for_input(instr);
+                    self.run_sound_routines()?;
 
-                    return false;
+                    return Ok(false);
                 }
                 _ => {
-                    self.handle_instruction(&instr);
+                    self.handle_instruction(&instr)?;
                 }
             }
         }
     }
 
+    // Web UI only - reads the operands of a read/read_char that's waiting on
+    // js, telling it how often to call back for a timed interrupt
+    #[allow(dead_code)]
+    fn pause_for_input(&mut self, instr: Instruction) {
+        self.paused_args = self.get_arguments(instr.operands.as_slice());
+
+        let time = Zmachine::timed_args(instr.opcode, &self.paused_args)
+            .map_or(0, |(time, _)| time);
+
+        if instr.opcode == Opcode::VAR_246 {
+            self.ui.message("read_char", &time.to_string());
+        } else {
+            let terminators = self.terminating_keys();
+            let preloaded = self.preloaded_input(self.paused_args[0]);
+
+            if !preloaded.is_empty() {
+                self.ui.message("preload", &preloaded);
+            }
+
+            if !terminators.is_empty() {
+                let msg = serde_json::to_string(&terminators).unwrap();
+                self.ui.message("terminators", &msg);
+            }
+
+            if time > 0 {
+                self.ui.message("timed", &time.to_string());
+            }
+        }
+
+        self.paused_instr = Some(instr);
+    }
+
+    // Web UI only - runs the interrupt routine for a paused timed read,
+    // `input` is whatever's been typed so far. Returns true if the routine
+    // aborted the read (control can go back to `step` in that case).
+    #[allow(dead_code)]
+    pub fn handle_tick(&mut self, input: String) -> Result<bool, ZmachineError> {
+        let opcode = match self.paused_instr {
+            Some(ref instr) => instr.opcode,
+            None => return Ok(false),
+        };
+
+        let routine = match Zmachine::timed_args(opcode, &self.paused_args) {
+            Some((_, routine)) => routine,
+            None => return Ok(false),
+        };
+
+        self.take_fault();
+        let aborted = self.call_interrupt(routine) != 0;
+
+        if let Some(cause) = self.take_fault() {
+            return Err(ZmachineError::new(self.pc, Some(opcode), cause));
+        }
+
+        if !aborted {
+            return Ok(false);
+        }
+
+        let instr = self.take_paused(&[opcode])?;
+
+        if opcode == Opcode::VAR_228 {
+            let parse_addr = self.paused_args.get(1).cloned().unwrap_or(0);
+            self.do_sread_second(self.paused_args[0], parse_addr, input);
+        }
+
+        self.process_result(&instr, 0);
+        self.check_fault(&instr).map(|_| true)
+    }
+
+    // Web UI only - the instruction that's waiting on js, which is an error
+    // if there isn't one of the `opcodes` waiting (js resuming twice, or
+    // with the wrong kind of answer). Anything else stays paused.
+    #[allow(dead_code)]
+    fn take_paused(&mut self, opcodes: &[Opcode]) -> Result<Instruction, ZmachineError> {
+        match self.paused_instr.take() {
+            Some(instr) if opcodes.contains(&instr.opcode) => Ok(instr),
+            other => {
+                let opcode = other.as_ref().map(|instr| instr.opcode);
+                self.paused_instr = other;
+
+                Err(ZmachineError::new(self.pc, opcode, Cause::NotPaused))
+            }
+        }
+    }
+
     // Web UI only - gives user input to the paused read instruction
     // (passes control back JS afterwards)
     #[allow(dead_code)]
-    pub fn handle_input(&mut self, input: String) {
-        let instr = self.paused_instr.take().expect(
-            "Can't handle input, no paused instruction to resume",
-        );
+    pub fn handle_input(&mut self, input: String, terminator: u16) -> Result<(), ZmachineError> {
+        let instr = self.take_paused(&[Opcode::VAR_228])?;
 
         // handle special debugging commands
         // these inputs shouldn't be processed normally
-        if self.is_debug_command(&input) {
+        if terminator == 13 && self.is_debug_command(&input) {
             if self.handle_debug_command(&input) {
                 self.ui.print("\n>");
             }
 
             // return execution to JS, which will read user input again:
-            return;
+            return Ok(());
         }
 
         // new input changes timelines, so remove any obsolete redos
@@ -1563,33 +2537,69 @@ impl Zmachine {
             self.undos.push(self.current_state.take().unwrap());
         }
 
-        // explicitly handle read (need to get args first)
-        let args = self.get_arguments(instr.operands.as_slice());
-        self.do_sread_second(args[0], args[1], input);
-        self.pc = instr.next;
+        // explicitly handle read (args were read when it paused)
+        let text_addr = self.paused_args[0];
+        let parse_addr = self.paused_args.get(1).cloned().unwrap_or(0);
+
+        self.do_sread_second(text_addr, parse_addr, input);
+        self.process_result(&instr, terminator);
+        self.check_fault(&instr)
+    }
+
+    // Web UI only - gives a single keypress (zscii) to the paused read_char
+    #[allow(dead_code)]
+    pub fn handle_key(&mut self, key: u16) -> Result<(), ZmachineError> {
+        let instr = self.take_paused(&[Opcode::VAR_246])?;
+
+        self.process_result(&instr, key);
+        self.check_fault(&instr)
     }
 
     // Web UI only
     #[allow(dead_code)]
-    pub fn restore(&mut self, data: &str) {
+    pub fn restore(&mut self, data: &str) -> Result<(), ZmachineError> {
+        let instr = self.take_paused(&[Opcode::OP0_182, Opcode::EXT_1001])?;
         let state = base64::decode(&data);
 
         // cancel restore (sending an empty string or if base64 decode fails)
         if data.is_empty() || state.is_err() {
-            let instr = self.paused_instr.take().unwrap();
             self.process_result(&instr, 0);
-        } else {
-            self.restore_state(state.unwrap().as_slice());
-            self.process_restore_result();
+            return self.check_fault(&instr);
+        }
+
+        match self.restore_state(state.unwrap().as_slice()) {
+            Ok(()) => self.process_restore_result(),
+            Err(err) => {
+                self.ui.print(&format!("Can't restore that save ({}).\n", err.cause));
+                self.process_result(&instr, 0);
+            }
         }
+
+        self.check_fault(&instr)
+    }
+
+    // Web UI only - fills the table of a paused auxiliary restore
+    // (sending an empty string means there wasn't any saved data)
+    #[allow(dead_code)]
+    pub fn restore_aux(&mut self, data: &str) -> Result<(), ZmachineError> {
+        let instr = self.take_paused(&[Opcode::EXT_1001])?;
+
+        let table = self.paused_args.first().cloned().unwrap_or(0) as usize;
+        let bytes = self.paused_args.get(1).cloned().unwrap_or(0) as usize;
+        let mut data = base64::decode(data).unwrap_or_default();
+
+        data.truncate(bytes);
+        self.memory.write(table, &data);
+        self.process_result(&instr, data.len() as u16);
+        self.check_fault(&instr)
     }
 
     // Web UI only
     // Loads a saved state _without_ processing a restore result (like the above)
     #[allow(dead_code)]
-    pub fn load_savestate(&mut self, data: &str) {
-        let state = base64::decode(data).unwrap();
-        self.restore_state(state.as_slice());
+    pub fn load_savestate(&mut self, data: &str) -> Result<(), ZmachineError> {
+        let state = base64::decode(data).unwrap_or_default();
+        self.restore_state(state.as_slice())
     }
 
     // Web UI only
@@ -1643,7 +2653,11 @@ impl Zmachine {
     }
 
     // OP2_6
-    fn do_jin(&self, obj1: u16, obj2: u16) -> u16 {
+    fn do_jin(&mut self, obj1: u16, obj2: u16) -> u16 {
+        if !self.check_object("jin", obj1) {
+            return 0;
+        }
+
         if self.get_parent(obj1) == obj2 { 1 } else { 0 }
     }
 
@@ -1663,18 +2677,26 @@ impl Zmachine {
     }
 
     // OP2_10
-    fn do_test_attr(&self, obj: u16, attr: u16) -> u16 {
+    fn do_test_attr(&mut self, obj: u16, attr: u16) -> u16 {
+        if !self.check_attr("test_attr", obj, attr) {
+            return 0;
+        }
+
         self.test_attr(obj, attr)
     }
 
     // OP2_11
     fn do_set_attr(&mut self, obj: u16, attr: u16) {
-        self.set_attr(obj, attr)
+        if self.check_attr("set_attr", obj, attr) {
+            self.set_attr(obj, attr)
+        }
     }
 
     // OP2_12
     fn do_clear_attr(&mut self, obj: u16, attr: u16) {
-        self.clear_attr(obj, attr)
+        if self.check_attr("clear_attr", obj, attr) {
+            self.clear_attr(obj, attr)
+        }
     }
 
     // OP2_13
@@ -1684,7 +2706,9 @@ impl Zmachine {
 
     // OP2_14
     fn do_insert_obj(&mut self, object: u16, destination: u16) {
-        self.insert_obj(object, destination);
+        if self.check_object("insert_obj", object) && self.check_object("insert_obj", destination) {
+            self.insert_obj(object, destination);
+        }
     }
 
     // OP2_15
@@ -1703,17 +2727,29 @@ impl Zmachine {
     }
 
     // OP2_17
-    fn do_get_prop(&self, object: u16, property_number: u16) -> u16 {
+    fn do_get_prop(&mut self, object: u16, property_number: u16) -> u16 {
+        if !self.check_object("get_prop", object) {
+            return 0;
+        }
+
         self.get_prop_value(object, property_number)
     }
 
     // OP2_18
-    fn do_get_prop_addr(&self, object: u16, property_number: u16) -> u16 {
+    fn do_get_prop_addr(&mut self, object: u16, property_number: u16) -> u16 {
+        if !self.check_object("get_prop_addr", object) {
+            return 0;
+        }
+
         self.get_prop_addr(object, property_number) as u16
     }
 
     // OP2_19
-    fn do_get_next_prop(&self, object: u16, property_number: u16) -> u16 {
+    fn do_get_next_prop(&mut self, object: u16, property_number: u16) -> u16 {
+        if !self.check_object("get_next_prop", object) {
+            return 0;
+        }
+
         self.get_next_prop(object, property_number)
     }
 
@@ -1733,13 +2769,52 @@ impl Zmachine {
     }
 
     // OP2_23
-    fn do_div(&self, a: u16, b: u16) -> u16 {
+    fn do_div(&mut self, a: u16, b: u16) -> u16 {
+        if b == 0 {
+            self.violation("div", "division by zero");
+            return 0;
+        }
+
         (a as i16).wrapping_div(b as i16) as u16
     }
 
     // OP2_24
-    fn do_mod(&self, a: u16, b: u16) -> u16 {
-        (a as i16 % b as i16) as u16
+    fn do_mod(&mut self, a: u16, b: u16) -> u16 {
+        if b == 0 {
+            self.violation("mod", "division by zero");
+            return 0;
+        }
+
+        (a as i16).wrapping_rem(b as i16) as u16
+    }
+
+    // OP2_27
+    fn do_set_colour(&mut self, fg: u16, bg: u16) {
+        // 0 = keep the current colour, 1 = default, 2-12 = standard colours
+        let convert = |colour: u16, current: Option<u16>| match colour {
+            1 => None,
+            2..=12 => Some(STANDARD_COLOURS[colour as usize - 2]),
+            _ => current,
+        };
+
+        self.style.foreground = convert(fg, self.style.foreground);
+        self.style.background = convert(bg, self.style.background);
+        self.apply_style();
+    }
+
+    // OP2_28
+    fn do_throw(&mut self, value: u16, frame: u16) {
+        let frame = frame as usize;
+
+        if frame == 0 || frame >= self.frames.len() {
+            self.fault(Cause::BadFrame(frame as u16));
+            return;
+        }
+
+        // unwind back to the frame that did the catch, then return from it
+        // (frame 1 is the one just above the dummy frame)
+        self.frames.truncate(frame + 1);
+        self.return_from_routine(value);
     }
 
     // OP1_128
@@ -1748,17 +2823,29 @@ impl Zmachine {
     }
 
     // OP1_129
-    fn do_get_sibling(&self, object: u16) -> u16 {
+    fn do_get_sibling(&mut self, object: u16) -> u16 {
+        if !self.check_object("get_sibling", object) {
+            return 0;
+        }
+
         self.get_sibling(object)
     }
 
     // OP1_130
-    fn do_get_child(&self, object: u16) -> u16 {
+    fn do_get_child(&mut self, object: u16) -> u16 {
+        if !self.check_object("get_child", object) {
+            return 0;
+        }
+
         self.get_child(object)
     }
 
     // OP1_131
-    fn do_get_parent(&self, object: u16) -> u16 {
+    fn do_get_parent(&mut self, object: u16) -> u16 {
+        if !self.check_object("get_parent", object) {
+            return 0;
+        }
+
         self.get_parent(object)
     }
 
@@ -1786,20 +2873,26 @@ impl Zmachine {
     // OP1_135
     fn do_print_addr(&mut self, addr: u16) {
         let zstring = self.read_zstring(addr as usize);
-        self.ui.print(&zstring);
+        self.print(&zstring);
     }
 
     // OP1_136 : call_1s
 
     // OP1_137
     fn do_remove_obj(&mut self, obj: u16) {
-        self.remove_obj(obj);
+        if self.check_object("remove_obj", obj) {
+            self.remove_obj(obj);
+        }
     }
 
     // OP1_138
     fn do_print_obj(&mut self, obj: u16) {
+        if !self.check_object("print_obj", obj) {
+            return;
+        }
+
         let name = self.get_object_name(obj);
-        self.ui.print_object(&name);
+        self.print_object(&name);
     }
 
     // OP1_139
@@ -1808,19 +2901,16 @@ impl Zmachine {
     }
 
     // OP1_140
-    fn do_jump(&mut self, offest: u16, instr: &Instruction) {
-        self.pc = if (offest as i16) < 0 {
-            instr.next - (-(offest as i16)) as usize - 2
-        } else {
-            instr.next + offest as usize - 2
-        };
+    fn do_jump(&mut self, offset: u16, instr: &Instruction) {
+        let offset = (offset as i16) as isize;
+        self.pc = (instr.next as isize + offset - 2) as usize;
     }
 
     // OP1_141
     fn do_print_paddr(&mut self, addr: u16) {
         let paddr = self.unpack_print_paddr(addr);
         let zstring = self.read_zstring(paddr);
-        self.ui.print(&zstring);
+        self.print(&zstring);
     }
 
     // OP1_142
@@ -1846,14 +2936,14 @@ impl Zmachine {
     // OP0_178
     fn do_print(&mut self, instr: &Instruction) {
         let text = instr.text.as_ref().expect("Can't print with no text!");
-        self.ui.print(text);
+        self.print(text);
     }
 
     // OP0_179
     fn do_print_ret(&mut self, instr: &Instruction) {
         let text = instr.text.as_ref().expect("Can't print with no text!");
-        self.ui.print(text);
-        self.ui.print("\n");
+        self.print(text);
+        self.print("\n");
         self.return_from_routine(1);
     }
 
@@ -1861,38 +2951,34 @@ impl Zmachine {
 
     // OP0_181
     fn do_save(&mut self, instr: &Instruction) {
-        let prompt = format!("\nFilename [{}]: ", self.save_name);
-        self.ui.print(&prompt);
+        let default = self.save_name.clone();
 
-        let input = self.ui.get_user_input();
-        let mut path = PathBuf::from(&self.save_dir);
-        let mut file;
-
-        match input.to_lowercase().as_ref() {
-            "" | "yes" | "y" => path.push(&self.save_name),
-            "no" | "n" | "cancel" => {
+        let path = match self.prompt_file_path(&default) {
+            Some(path) => path,
+            None => {
                 self.process_result(instr, 0);
                 return;
             }
-            _ => path.push(input),
-        }
+        };
+
+        // The save PC points to either the save instructions branch data or store
+        // data. In either case, this is the last byte of the instruction. (so -1)
+        let pc = instr.next - 1;
+        let data = self.make_save_state(pc);
 
-        if let Ok(handle) = File::create(&path) {
-            file = handle;
-        } else {
+        let saved = File::create(&path).and_then(|mut file| file.write_all(&data[..]));
+
+        if saved.is_err() {
             self.ui.print("Can't save to that file, try another?\n");
             self.process_result(instr, 0);
             return;
         }
 
         // save file name for next use
-        self.save_name = path.file_name().unwrap().to_string_lossy().into_owned();
-
-        // The save PC points to either the save instructions branch data or store
-        // data. In either case, this is the last byte of the instruction. (so -1)
-        let pc = instr.next - 1;
-        let data = self.make_save_state(pc);
-        file.write_all(&data[..]).expect("Error saving to file");
+        self.save_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(default);
 
         self.process_save_result(instr);
     }
@@ -1905,40 +2991,40 @@ impl Zmachine {
 
     // OP0_182
     fn do_restore(&mut self, instr: &Instruction) {
-        let prompt = format!("\nFilename [{}]: ", self.save_name);
-        self.ui.print(&prompt);
-
-        let input = self.ui.get_user_input();
-        let mut path = PathBuf::from(&self.save_dir);
+        let default = self.save_name.clone();
         let mut data = Vec::new();
-        let mut file;
 
-        match input.to_lowercase().as_ref() {
-            "" | "yes" | "y" => path.push(&self.save_name),
-            "no" | "n" | "cancel" => {
+        let path = match self.prompt_file_path(&default) {
+            Some(path) => path,
+            None => {
                 self.process_result(instr, 0);
                 return;
             }
-            _ => path.push(input),
-        }
+        };
 
-        if let Ok(handle) = File::open(&path) {
-            file = handle;
-        } else {
+        // (directories open fine, it's reading them that fails)
+        let read = File::open(&path).and_then(|mut file| file.read_to_end(&mut data));
+
+        if read.is_err() {
             self.ui.print("Can't open that file, try another?\n");
             self.process_result(instr, 0);
             return;
         }
 
         // save file name for next use
-        self.save_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        self.save_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(default);
 
         // restore program counter position, stack frames, and dynamic memory
-        file.read_to_end(&mut data).expect(
-            "Error reading save file",
-        );
-        self.restore_state(data.as_slice());
-        self.process_restore_result();
+        match self.restore_state(data.as_slice()) {
+            Ok(()) => self.process_restore_result(),
+            Err(err) => {
+                self.ui.print(&format!("Can't restore that file ({}).\n", err.cause));
+                self.process_result(instr, 0);
+            }
+        }
     }
 
     fn process_restore_result(&mut self) {
@@ -1967,10 +3053,22 @@ impl Zmachine {
 
     // OP0_183
     fn do_restart(&mut self) {
+        self.ui.erase_window(-1);
+        self.style = Style::default();
+        self.font = 1;
+        self.apply_style();
+
+        self.window = 0;
+        self.memory_streams.clear();
+        self.undo_saves.clear();
+        self.unload_sounds();
+
         self.pc = self.initial_pc;
         self.frames.clear();
         self.frames.push(Frame::empty());
-        self.memory.write(0, self.original_dynamic.as_slice());
+
+        let original = self.original_dynamic.clone();
+        self.replace_dynamic_memory(&original);
     }
 
     // OP0_184
@@ -1979,14 +3077,22 @@ impl Zmachine {
         self.return_from_routine(value);
     }
 
-    // OP0_185
+    // OP0_185 (v1-4)
     fn do_pop(&mut self) {
         self.stack_pop();
     }
 
+    // OP0_185 (v5+)
+    fn do_catch(&self) -> u16 {
+        // the frame's identifier is its depth in the call stack, counting
+        // from the first real routine (the dummy frame can't be thrown to, so
+        // catching outside of any routine gives 0, which is never valid)
+        (self.frames.len() - 1) as u16
+    }
+
     // OP0_187
     fn do_newline(&mut self) {
-        self.ui.print("\n");
+        self.print("\n");
     }
 
     // OP0_188
@@ -1995,8 +3101,14 @@ impl Zmachine {
     }
 
     // OP0_189
+    // The checksum was taken from the original file when it was loaded, so
+    // changes to dynamic memory since then don't matter
     fn do_verify(&self) -> u16 {
-        1
+        if self.checksum == self.memory.read_word(0x1C) {
+            1
+        } else {
+            0
+        }
     }
 
     // All calls:
@@ -2013,6 +3125,11 @@ impl Zmachine {
             return;
         }
 
+        self.push_routine(addr, args, instr.next, instr.store);
+    }
+
+    // Enters a routine, returning to `resume` (and storing into `store`) after
+    fn push_routine(&mut self, addr: u16, args: &[u16], resume: usize, store: Option<u8>) {
         // decode routine / prepopulate routine local variables
         let routine_addr = self.unpack_routine_addr(addr);
         let mut read = self.memory.get_reader(routine_addr);
@@ -2027,8 +3144,13 @@ impl Zmachine {
             };
         }
 
+        // (the routine header was out of bounds)
+        if self.has_fault() {
+            return;
+        }
+
         let first_instr = read.position();
-        let frame = Frame::new(instr.next, instr.store, locals, args);
+        let frame = Frame::new(resume, store, locals, args);
 
         self.pc = first_instr;
         self.frames.push(frame);
@@ -2039,6 +3161,11 @@ impl Zmachine {
         let word_index = index.wrapping_mul(2);
         let word_addr = array_addr.wrapping_add(word_index);
 
+        // (the write still happens, like other interpreters do)
+        if word_addr as usize + 1 >= self.static_start {
+            self.violation("storew", &format!("@storew to static memory @ {:#06x}", word_addr));
+        }
+
         self.memory.write_word(word_addr as usize, value);
     }
 
@@ -2046,29 +3173,50 @@ impl Zmachine {
     fn do_storeb(&mut self, array: u16, index: u16, value: u16) {
         let word_addr = array.wrapping_add(index);
 
+        if word_addr as usize >= self.static_start {
+            self.violation("storeb", &format!("@storeb to static memory @ {:#06x}", word_addr));
+        }
+
         self.memory.write_byte(word_addr as usize, value as u8);
     }
 
     // VAR_227
     fn do_put_prop(&mut self, obj: u16, prop: u16, value: u16) {
-        self.put_prop(obj, prop, value);
+        if self.check_object("put_prop", obj) {
+            self.put_prop(obj, prop, value);
+        }
     }
 
     // VAR_228
-    fn do_sread(&mut self, instr: &Instruction, text_addr: u16, parse_addr: u16) {
+    fn do_sread(&mut self, instr: &Instruction, args: &[u16]) {
+        let text_addr = args[0];
+        let parse_addr = args.get(1).cloned().unwrap_or(0);
+
+        let terminators = self.terminating_keys();
+        let preloaded = self.preloaded_input(text_addr);
+
         // need to update the status bar before each read
         self.update_status_bar();
-        // add extra space so it doesn't look janky (non-spec)
-        self.ui.print(" ");
+        // add extra space so it doesn't look janky (non-spec), unless the
+        // input is carrying on from text that's already on screen
+        if preloaded.is_empty() {
+            self.ui.print(" ");
+        }
 
-        let input = self.ui.get_user_input();
+        let (input, terminator) = match Zmachine::timed_args(instr.opcode, args) {
+            Some((time, routine)) => self.read_timed_command(preloaded, time, routine, &terminators),
+            None if !terminators.is_empty() || !preloaded.is_empty() => {
+                self.read_timed_command(preloaded, 0, 0, &terminators)
+            }
+            None => (self.read_command(), 13),
+        };
 
         // handle special debugging commands
         // these inputs shouldn't be processed normally
-        if self.is_debug_command(&input) {
+        if terminator == 13 && self.is_debug_command(&input) {
             if self.handle_debug_command(&input) {
                 self.ui.print("\n>");
-                self.do_sread(instr, text_addr, parse_addr);
+                self.do_sread(instr, args);
             }
 
             return;
@@ -2076,6 +3224,11 @@ impl Zmachine {
 
         self.do_sread_second(text_addr, parse_addr, input);
 
+        // v5+ stores the terminating character (0 if interrupted)
+        if let Some(index) = instr.store {
+            self.write_variable(index, terminator);
+        }
+
         // save state JUST after having processed user input
         // new input changes timelines, so remove any obsolete redos
         self.redos.clear();
@@ -2091,6 +3244,24 @@ impl Zmachine {
         self.current_state = Some((location, state));
     }
 
+    // In v5+ the text buffer can already hold some input (byte 1 is how
+    // much), which the game has printed and which can still be edited
+    fn preloaded_input(&self, text_addr: u16) -> String {
+        if self.version < 5 {
+            return String::new();
+        }
+
+        let addr = text_addr as usize;
+        let max_length = self.memory.read_byte(addr) as usize;
+        let length = cmp::min(self.memory.read_byte(addr + 1) as usize, max_length);
+
+        self.memory
+            .read(addr + 2, length)
+            .iter()
+            .filter_map(|&zscii| zscii_to_char(u16::from(zscii), &self.unicode_table))
+            .collect()
+    }
+
     fn do_sread_second(&mut self, text_addr: u16, parse_addr: u16, mut raw: String) {
         let text_addr = text_addr as usize;
         let parse_addr = parse_addr as usize;
@@ -2101,36 +3272,50 @@ impl Zmachine {
             max_length -= 1;
         }
 
-        raw.truncate(max_length as usize);
-        let input = &raw.to_lowercase();
+        raw = raw.chars().take(max_length as usize).collect();
+        self.record_input(&raw);
+
+        // anything that doesn't have a zscii equivalent becomes a "?"
+        let input: String = raw
+            .to_lowercase()
+            .chars()
+            .map(|chr| char_to_zscii(chr, &self.unicode_table).map_or('?', |_| chr))
+            .take(max_length as usize)
+            .collect();
 
-        let bytes = input.as_bytes();
+        let bytes: Vec<u8> = input
+            .chars()
+            .map(|chr| char_to_zscii(chr, &self.unicode_table).unwrap_or(0) as u8)
+            .collect();
         let len = bytes.len();
 
         // ver 1-4 start storing @ byte 1, ending with a terminating 0
         // ver 5+ save the input length @1, start storing @2, and DON'T end with 0
         if self.version <= 4 {
-            self.memory.write(text_addr + 1, bytes);
+            self.memory.write(text_addr + 1, &bytes);
             self.memory.write_byte(text_addr + 1 + len, 0);
         } else {
             self.memory.write_byte(text_addr + 1, len as u8);
-            self.memory.write(text_addr + 2, bytes);
+            self.memory.write(text_addr + 2, &bytes);
         }
 
         // skip tokenization step if parse_addr is 0
         if parse_addr != 0 {
-            self.tokenise(input, parse_addr);
+            let dict_addr = self.memory.read_word(0x08) as usize;
+            self.tokenise(&input, parse_addr, dict_addr, false);
         }
     }
 
     // VAR_229
     fn do_print_char(&mut self, chr: u16) {
-        self.ui.print(&(chr as u8 as char).to_string());
+        if let Some(chr) = zscii_to_char(chr, &self.unicode_table) {
+            self.print(&chr.to_string());
+        }
     }
 
     // VAR_230
     fn do_print_num(&mut self, signed: u16) {
-        self.ui.print(&(signed as i16).to_string());
+        self.print(&(signed as i16).to_string());
     }
 
     // VAR_231
@@ -2160,8 +3345,414 @@ impl Zmachine {
         value
     }
 
+    // VAR_234
+    fn do_split_window(&mut self, lines: u16) {
+        self.ui.split_window(lines);
+    }
+
+    // VAR_235
+    fn do_set_window(&mut self, window: u16) {
+        self.window = window;
+        self.ui.set_window(window);
+    }
+
+    // VAR_237
+    fn do_erase_window(&mut self, window: u16) {
+        if window as i16 == -1 {
+            self.window = 0;
+        }
+
+        self.ui.erase_window(window as i16);
+    }
+
+    // VAR_238
+    fn do_erase_line(&mut self, value: u16) {
+        // any value other than 1 does nothing (v4/5)
+        if value == 1 {
+            self.ui.erase_line();
+        }
+    }
+
+    // VAR_239
+    fn do_set_cursor(&mut self, line: u16, column: u16) {
+        self.ui.set_cursor(line, column);
+    }
+
+    // VAR_240
+    fn do_get_cursor(&mut self, array: u16) {
+        let (line, column) = self.ui.get_cursor();
+        let mut write = self.memory.get_writer(array as usize);
+
+        write.word(line);
+        write.word(column);
+    }
+
+    // VAR_241
+    fn do_set_text_style(&mut self, style: u16) {
+        // 0 = roman (turns everything off), other styles combine
+        if style == 0 {
+            self.style.reverse = false;
+            self.style.bold = false;
+            self.style.italic = false;
+            self.style.fixed = false;
+        } else {
+            self.style.reverse |= style & 1 != 0;
+            self.style.bold |= style & 2 != 0;
+            self.style.italic |= style & 4 != 0;
+            self.style.fixed |= style & 8 != 0;
+        }
+
+        self.apply_style();
+    }
+
+    // VAR_243
+    fn do_output_stream(&mut self, number: u16, table: u16) {
+        let flags = self.memory.read_word(0x10);
+
+        match number as i16 {
+            1 => self.screen_stream = true,
+            -1 => self.screen_stream = false,
+            2 => {
+                self.memory.write_word(0x10, flags | 1);
+                self.sync_transcript();
+            }
+            -2 => {
+                self.memory.write_word(0x10, flags & !1);
+                self.sync_transcript();
+            }
+            3 => {
+                if self.memory_streams.len() == 16 {
+                    self.fault(Cause::StreamOverflow);
+                    return;
+                }
+
+                self.memory_streams.push((table as usize, 0));
+            }
+            -3 => {
+                // the table's first word gets the number of bytes written
+                if let Some((table, written)) = self.memory_streams.pop() {
+                    self.memory.write_word(table, written as u16);
+                }
+            }
+            4 if self.command_record.is_none() => {
+                self.command_record = self.open_stream_file("rec");
+            }
+            -4 => self.command_record = None,
+            _ => (),
+        }
+    }
+
+    // VAR_244
+    fn do_input_stream(&mut self, number: u16) {
+        match number {
+            0 => self.command_script.clear(),
+            1 => {
+                let path = self.stream_file_path("rec");
+
+                if !self.load_command_file(&path) {
+                    let msg = format!("\n[Can't open command file: {}]\n", path.display());
+                    self.ui.print(&msg);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // VAR_245
+    // Sounds 1 & 2 are bleeps, the rest are blorb resources which get
+    // prepared (1), started (2), stopped (3) and finished with (4)
+    fn do_sound_effect(&mut self, args: &[u16]) {
+        let number = args.first().cloned().unwrap_or(1);
+        let effect = args.get(1).cloned().unwrap_or(2);
+
+        if number == 1 || number == 2 {
+            self.ui.bleep(number);
+            return;
+        }
+
+        match effect {
+            1 => {
+                self.prepare_sound(number);
+            }
+            2 => {
+                let volume = args.get(2).cloned().unwrap_or(0xFF);
+                let routine = args.get(3).cloned().unwrap_or(0);
+                self.start_sound(number, volume, routine);
+            }
+            3 => {
+                self.sound_routines.remove(&number);
+                self.ui.stop_sound(number);
+            }
+            4 => {
+                self.sound_routines.remove(&number);
+                if self.loaded_sounds.remove(&number) {
+                    self.ui.unload_sound(number);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn has_sounds(&self) -> bool {
+        self.resources.as_ref().is_some_and(|blorb| {
+            blorb.resources.iter().any(|res| res.usage == Usage::Sound)
+        })
+    }
+
+    // hands the sound data over to the UI, false if it can't be played
+    fn prepare_sound(&mut self, number: u16) -> bool {
+        if self.loaded_sounds.contains(&number) {
+            return true;
+        }
+
+        if !self.ui.supports_sound() {
+            return false;
+        }
+
+        let sound = self
+            .resources
+            .as_ref()
+            .and_then(|blorb| blorb.get(Usage::Sound, u32::from(number)));
+
+        match sound {
+            Some(sound) => self.ui.load_sound(number, &sound.kind, &sound.data),
+            None => return false,
+        }
+
+        self.loaded_sounds.insert(number);
+        true
+    }
+
+    // The low byte of `volume` is 1-8 (255 = loudest), and the high byte is
+    // how many times to play it (255 = forever). v3 games don't give repeats,
+    // those come from the blorb's loop chunk instead (0 = forever).
+    fn start_sound(&mut self, number: u16, volume: u16, routine: u16) {
+        if !self.prepare_sound(number) {
+            // a sound that can't be played is over straight away
+            if routine != 0 && self.version >= 5 {
+                self.call_interrupt(routine);
+            }
+            return;
+        }
+
+        let level = match volume & 0xFF {
+            0 => 1,
+            level => cmp::min(level, 8) as u8,
+        };
+
+        let repeats = if self.version >= 5 {
+            match volume >> 8 {
+                0 => 1,
+                repeats => repeats as u8,
+            }
+        } else {
+            let looping = self
+                .resources
+                .as_ref()
+                .and_then(|blorb| blorb.loops.get(&u32::from(number)).cloned());
+
+            match looping {
+                Some(0) => 255,
+                Some(repeats) => cmp::min(repeats, 254) as u8,
+                None => 1,
+            }
+        };
+
+        if routine != 0 && self.version >= 5 {
+            self.sound_routines.insert(number, routine);
+        }
+
+        self.ui.start_sound(number, level, repeats);
+    }
+
+    fn unload_sounds(&mut self) {
+        for number in self.loaded_sounds.drain() {
+            self.ui.unload_sound(number);
+        }
+
+        self.sound_routines.clear();
+        self.finished_sounds.clear();
+    }
+
+    // Web UI only - a sound has finished playing, so call its routine (if it
+    // had one) as an interrupt while waiting for input. If the game's busy
+    // running, the routine waits for the next pause instead.
+    #[allow(dead_code)]
+    pub fn handle_sound_finished(&mut self, number: u16) -> Result<(), ZmachineError> {
+        if let Some(routine) = self.sound_routines.remove(&number) {
+            self.finished_sounds.push_back(routine);
+        }
+
+        if self.paused_instr.is_some() {
+            self.run_sound_routines()?;
+        }
+
+        Ok(())
+    }
+
+    // Web UI only - calls the routines of any sounds that have finished, in
+    // the order they finished
+    #[allow(dead_code)]
+    fn run_sound_routines(&mut self) -> Result<(), ZmachineError> {
+        while let Some(routine) = self.finished_sounds.pop_front() {
+            self.take_fault();
+            self.call_interrupt(routine);
+
+            if let Some(cause) = self.take_fault() {
+                return Err(ZmachineError::new(self.pc, None, cause));
+            }
+        }
+
+        Ok(())
+    }
+
+    // VAR_246
+    fn do_read_char(&mut self, args: &[u16]) -> u16 {
+        // a playback file supplies one key per line (blank line = return)
+        let key = match self.command_script.pop_front() {
+            Some(line) => line.chars().next().map_or(13, |chr| {
+                char_to_zscii(chr, &self.unicode_table).unwrap_or(u16::from(b'?'))
+            }),
+            None => match Zmachine::timed_args(Opcode::VAR_246, args) {
+                Some((time, routine)) => self.read_timed_key(time, routine),
+                None => self.ui.get_user_key(0).unwrap_or(13),
+            },
+        };
+
+        let line = match zscii_to_char(key, &self.unicode_table) {
+            Some(chr) if key != 13 => chr.to_string(),
+            _ => String::new(),
+        };
+
+        if let Some(ref mut file) = self.command_record {
+            writeln!(file, "{}", line).ok();
+        }
+
+        key
+    }
+
+    // VAR_247
+    fn do_scan_table(&self, x: u16, table: u16, len: u16, form: u16) -> u16 {
+        // form: top bit set = compare words (otherwise bytes),
+        // bottom 7 bits = length of each field in the table
+        let words = form & 0x80 != 0;
+        let field = (form & 0x7F) as usize;
+        let table = table as usize;
+
+        (0..len as usize)
+            .map(|n| table + n * field)
+            .find(|&addr| {
+                if words {
+                    self.memory.read_word(addr) == x
+                } else {
+                    u16::from(self.memory.read_byte(addr)) == x
+                }
+            })
+            .map_or(0, |addr| addr as u16)
+    }
+
     // VAR_248 do_not() (same as OP1_143)
 
+    // VAR_251
+    fn do_tokenise(&mut self, text_addr: u16, parse_addr: u16, dict_addr: u16, flag: u16) {
+        let text_addr = text_addr as usize;
+
+        // text is stored the same way aread/sread leave it
+        let bytes = if self.version <= 4 {
+            self.memory
+                .read(text_addr + 1, self.memory.read_byte(text_addr) as usize)
+                .iter()
+                .cloned()
+                .take_while(|&zscii| zscii != 0)
+                .collect::<Vec<_>>()
+        } else {
+            let len = self.memory.read_byte(text_addr + 1) as usize;
+            self.memory.read(text_addr + 2, len).to_vec()
+        };
+
+        let text: String = bytes
+            .iter()
+            .map(|&zscii| zscii_to_char(u16::from(zscii), &self.unicode_table).unwrap_or('?'))
+            .collect();
+
+        let dict_addr = match dict_addr {
+            0 => self.memory.read_word(0x08) as usize,
+            addr => addr as usize,
+        };
+
+        self.tokenise(&text, parse_addr as usize, dict_addr, flag != 0);
+    }
+
+    // VAR_252
+    fn do_encode_text(&mut self, text_addr: u16, len: u16, from: u16, coded_addr: u16) {
+        let start = text_addr as usize + from as usize;
+
+        let text: String = self
+            .memory
+            .read(start, len as usize)
+            .iter()
+            .map(|&zscii| zscii_to_char(u16::from(zscii), &self.unicode_table).unwrap_or('?'))
+            .collect();
+
+        let encoded = self.encode_zstring(&text);
+        self.memory.write(coded_addr as usize, &encoded);
+    }
+
+    // VAR_253
+    fn do_copy_table(&mut self, first: u16, second: u16, size: u16) {
+        let first = first as usize;
+        let second = second as usize;
+        let size = size as i16;
+        let len = size.unsigned_abs() as usize;
+
+        // no second table means zero out the first one
+        if second == 0 {
+            self.memory.write(first, &vec![0; len]);
+            return;
+        }
+
+        // A negative size forces a forward copy, even if the tables overlap
+        // and that corrupts the source. Otherwise copy it all in one go, so
+        // overlapping tables still come out right.
+        if size < 0 {
+            for n in 0..len {
+                let byte = self.memory.read_byte(first + n);
+                self.memory.write_byte(second + n, byte);
+            }
+        } else {
+            let data = self.memory.read(first, len).to_vec();
+            self.memory.write(second, &data);
+        }
+    }
+
+    // VAR_254
+    fn do_print_table(&mut self, table: u16, width: u16, height: u16, skip: u16) {
+        let (line, column) = self.ui.get_cursor();
+        let table = table as usize;
+        let stride = (width + skip) as usize;
+
+        for row in 0..height {
+            // each line starts below the last, at the same column
+            if row > 0 {
+                if self.window == 1 && self.memory_streams.is_empty() {
+                    self.ui.set_cursor(line + row, column);
+                } else {
+                    self.print("\n");
+                }
+            }
+
+            let start = table + row as usize * stride;
+            let text: String = self
+                .memory
+                .read(start, width as usize)
+                .iter()
+                .filter_map(|&zscii| zscii_to_char(u16::from(zscii), &self.unicode_table))
+                .collect();
+
+            self.print(&text);
+        }
+    }
+
     // VAR_255
     fn do_check_arg_count(&self, num: u16) -> u16 {
         let count = u16::from(
@@ -2200,6 +3791,127 @@ impl Zmachine {
         }
         (number as i16) as u16
     }
+
+    // EXT_1004
+    // Returns the previous font (0 = unavailable, and asking for font 0 just
+    // returns the current one). The picture font (2) isn't available.
+    fn do_set_font(&mut self, font: u16) -> u16 {
+        let previous = self.font;
+
+        match font {
+            0 => previous,
+            1 | 3 | 4 => {
+                self.font = font;
+                self.apply_style();
+                previous
+            }
+            _ => 0,
+        }
+    }
+
+    // EXT_1000 (save table bytes name prompt)
+    fn do_save_table(&mut self, instr: &Instruction, args: &[u16]) {
+        let (table, bytes) = (args[0] as usize, args[1] as usize);
+        let name = self.aux_file_name(args.get(2).cloned().unwrap_or(0));
+
+        // the game can ask to skip the prompt (0) and just use its name
+        let prompt = args.get(3) != Some(&0);
+        let path = self.aux_file_path(&name, prompt);
+
+        let saved = path
+            .and_then(|path| File::create(path).ok())
+            .is_some_and(|mut file| {
+                file.write_all(self.memory.read(table, bytes)).is_ok()
+            });
+
+        if prompt && !saved {
+            self.ui.print("Can't save to that file.\n");
+        }
+
+        self.process_result(instr, if saved { 1 } else { 0 });
+    }
+
+    // EXT_1001 (restore table bytes name prompt)
+    fn do_restore_table(&mut self, instr: &Instruction, args: &[u16]) {
+        let (table, bytes) = (args[0] as usize, args[1] as usize);
+        let name = self.aux_file_name(args.get(2).cloned().unwrap_or(0));
+        let mut data = Vec::new();
+
+        let prompt = args.get(3) != Some(&0);
+        let path = self.aux_file_path(&name, prompt);
+
+        let read = path
+            .and_then(|path| File::open(path).ok())
+            .is_some_and(|mut file| file.read_to_end(&mut data).is_ok());
+
+        if prompt && !read {
+            self.ui.print("Can't open that file.\n");
+        }
+
+        // the result is the number of bytes actually read into the table
+        data.truncate(bytes);
+        self.memory.write(table, &data);
+        self.process_result(instr, data.len() as u16);
+    }
+
+    // EXT_1009
+    fn do_save_undo(&mut self, instr: &Instruction) -> u16 {
+        // same as a regular save, the pc points at the store byte
+        let state = self.make_save_state(instr.next - 1);
+        self.undo_saves.push_back(state);
+
+        if self.undo_saves.len() > UNDO_SLOTS {
+            self.undo_saves.pop_front();
+        }
+
+        1
+    }
+
+    // EXT_1010
+    fn do_restore_undo(&mut self, instr: &Instruction) {
+        match self.undo_saves.pop_back() {
+            Some(ref state) if self.restore_state(state.as_slice()).is_ok() => {
+                self.process_restore_result();
+            }
+            _ => self.process_result(instr, 0),
+        }
+    }
+
+    // EXT_1011
+    fn do_print_unicode(&mut self, chr: u16) {
+        let chr = char::from_u32(u32::from(chr)).unwrap_or('?');
+        self.print(&chr.to_string());
+    }
+
+    // EXT_1012
+    fn do_check_unicode(&self, chr: u16) -> u16 {
+        // bit 0: can be printed, bit 1: can be typed (has a zscii equivalent)
+        match char::from_u32(u32::from(chr)) {
+            Some(chr) if !chr.is_control() => {
+                if char_to_zscii(chr, &self.unicode_table).is_some() {
+                    0b11
+                } else {
+                    0b01
+                }
+            }
+            _ => 0,
+        }
+    }
+
+    // EXT_1013
+    fn do_set_true_colour(&mut self, fg: u16, bg: u16) {
+        // -1 = default, -2 = keep current (also -3/-4, which only make sense
+        // in v6), anything else is a 15-bit colour value
+        let convert = |colour: u16, current: Option<u16>| match colour as i16 {
+            -1 => None,
+            x if x < 0 => current,
+            _ => Some(colour),
+        };
+
+        self.style.foreground = convert(fg, self.style.foreground);
+        self.style.background = convert(bg, self.style.background);
+        self.apply_style();
+    }
 }
 
 // debug functions
@@ -2568,16 +4280,19 @@ impl Zmachine {
 
             set.insert(instr);
 
-            if let Some(addr) = branch {
-                follow(zvm, set, zvm.decode_instruction(addr));
+            // (stops following anything that doesn't decode)
+            if let Some(Ok(instr)) = branch.map(|addr| zvm.decode_instruction(addr)) {
+                follow(zvm, set, instr);
             };
 
-            if let Some(addr) = next {
-                follow(zvm, set, zvm.decode_instruction(addr));
+            if let Some(Ok(instr)) = next.map(|addr| zvm.decode_instruction(addr)) {
+                follow(zvm, set, instr);
             };
         };
 
-        follow(self, &mut set, first_instr);
+        if let Ok(instr) = first_instr {
+            follow(self, &mut set, instr);
+        }
 
         let mut instructions = set.iter().collect::<Vec<_>>();
         instructions.sort_by_key(|i| i.addr);
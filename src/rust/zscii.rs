@@ -0,0 +1,35 @@
+// Converting between zscii and unicode. Zscii 155 and up map through the
+// game's unicode translation table (spec 3.8.5), which the zmachine loads and
+// hands to the UI so typed keys get converted the same way.
+
+// unicode equivalents of zscii 155-223 when a game doesn't have its own
+// unicode translation table (spec 3.8.7)
+pub const DEFAULT_UNICODE: [char; 69] = [
+    'ä', 'ö', 'ü', 'Ä', 'Ö', 'Ü', 'ß', '»', '«', 'ë', 'ï', 'ÿ', 'Ë', 'Ï', 'á', 'é', 'í', 'ó', 'ú',
+    'ý', 'Á', 'É', 'Í', 'Ó', 'Ú', 'Ý', 'à', 'è', 'ì', 'ò', 'ù', 'À', 'È', 'Ì', 'Ò', 'Ù', 'â', 'ê',
+    'î', 'ô', 'û', 'Â', 'Ê', 'Î', 'Ô', 'Û', 'å', 'Å', 'ø', 'Ø', 'ã', 'ñ', 'õ', 'Ã', 'Ñ', 'Õ', 'æ',
+    'Æ', 'ç', 'Ç', 'þ', 'ð', 'Þ', 'Ð', '£', 'œ', 'Œ', '¡', '¿',
+];
+
+// zscii -> unicode, `None` for codes that don't print anything (3.8)
+pub fn zscii_to_char(zscii: u16, unicode_table: &[char]) -> Option<char> {
+    match zscii {
+        13 => Some('\n'),
+        32..=126 => Some(zscii as u8 as char),
+        155..=251 => Some(*unicode_table.get(zscii as usize - 155).unwrap_or(&'?')),
+        _ => None,
+    }
+}
+
+// unicode -> zscii, `None` if the character can't be represented
+pub fn char_to_zscii(chr: char, unicode_table: &[char]) -> Option<u16> {
+    match chr {
+        '\n' => Some(13),
+        ' '..='~' => Some(chr as u16),
+        _ => unicode_table
+            .iter()
+            .take(97)
+            .position(|&extra| extra == chr)
+            .map(|index| index as u16 + 155),
+    }
+}